@@ -0,0 +1,110 @@
+use rocket::request::{self, FromRequest, Outcome, Request};
+use sea_orm::*;
+
+use super::throttle::ClientAddr;
+use crate::entities::{auth_event, prelude::*};
+
+// 安全审计事件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventType {
+    SignIn,
+    SignInFailed,
+    AccountLocked,
+    SignUp,
+    SignOut,
+    SignOutAll,
+    RefreshReuse,
+    TokenInvalid,
+    TwoFactorFailed,
+    PasswordChanged,
+    PasswordReset,
+    OidcSignIn,
+    ImpersonationStarted,
+    Impersonation,
+    RoleChanged,
+}
+
+impl EventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventType::SignIn => "sign_in",
+            EventType::SignInFailed => "sign_in_failed",
+            EventType::AccountLocked => "account_locked",
+            EventType::SignUp => "sign_up",
+            EventType::SignOut => "sign_out",
+            EventType::SignOutAll => "sign_out_all",
+            EventType::RefreshReuse => "refresh_reuse",
+            EventType::TokenInvalid => "token_invalid",
+            EventType::TwoFactorFailed => "two_factor_failed",
+            EventType::PasswordChanged => "password_changed",
+            EventType::PasswordReset => "password_reset",
+            EventType::OidcSignIn => "oidc_sign_in",
+            EventType::ImpersonationStarted => "impersonation_started",
+            EventType::Impersonation => "impersonation",
+            EventType::RoleChanged => "role_changed",
+        }
+    }
+}
+
+// 请求来源：客户端IP和User-Agent
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl ClientInfo {
+    // 记录一条审计事件。写入失败不影响请求本身，只输出错误日志
+    pub async fn record(
+        &self,
+        db: &DatabaseConnection,
+        user_id: Option<i32>,
+        event: EventType,
+        detail: Option<String>,
+    ) {
+        if let Err(err) = self.try_record(db, user_id, event, detail).await {
+            error!("写入审计日志失败：{}", err);
+        }
+    }
+
+    // 审计记录必须成功的操作使用，失败时返回错误
+    pub async fn try_record(
+        &self,
+        db: &DatabaseConnection,
+        user_id: Option<i32>,
+        event: EventType,
+        detail: Option<String>,
+    ) -> Result<(), DbErr> {
+        AuthEvent::insert(auth_event::ActiveModel {
+            user_id: Set(user_id),
+            event_type: Set(event.as_str().to_string()),
+            ip: Set(self.ip.to_owned()),
+            user_agent: Set(self.user_agent.to_owned()),
+            detail: Set(detail.map(|d| d.chars().take(255).collect())),
+            ..Default::default()
+        })
+        .exec(db)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientInfo {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let ip = match req.guard::<ClientAddr>().await {
+            Outcome::Success(client) => Some(client.ip.to_string()),
+            _ => None,
+        };
+
+        Outcome::Success(ClientInfo {
+            ip,
+            user_agent: req
+                .headers()
+                .get_one("User-Agent")
+                .map(|ua| ua.chars().take(255).collect()),
+        })
+    }
+}
@@ -0,0 +1,254 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use jsonwebtoken::{
+    decode, decode_header, encode,
+    errors::{Error, ErrorKind},
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use rocket::serde::{de::DeserializeOwned, Serialize};
+use rsa::{
+    pkcs1::DecodeRsaPublicKey, pkcs8::DecodePublicKey, traits::PublicKeyParts, RsaPublicKey,
+};
+
+use crate::AppConfig;
+
+// JWT签名和验证密钥。
+// 配置了密钥目录时使用RS256/EdDSA：<kid>.key.pem为私钥，<kid>.pub.pem为公钥，
+// 目录中所有公钥都可用于验证，便于轮换；未配置时退回到HS256共享密钥。
+// 配置了密钥目录后，只有开启BOOKSTORE_JWT_ACCEPT_LEGACY_HS256才接受没有kid的HS256旧令牌。
+pub struct KeyStore {
+    signing: Option<(String, Algorithm, EncodingKey)>,
+    verifying: HashMap<String, (Algorithm, DecodingKey)>,
+    secret: Option<(EncodingKey, DecodingKey)>,
+    jwks: JwkSet,
+}
+
+impl KeyStore {
+    pub fn from_config(config: &AppConfig) -> Self {
+        let secret = config.jwt_sercert.as_ref().map(|s| {
+            (
+                EncodingKey::from_secret(s.as_bytes()),
+                DecodingKey::from_secret(s.as_bytes()),
+            )
+        });
+
+        let dir = match &config.jwt_keys_dir {
+            Some(dir) => Path::new(dir),
+            None => {
+                if secret.is_none() {
+                    panic!("Please set the BOOKSTORE_JWT_SECRET or BOOKSTORE_JWT_KEYS_DIR env variable.");
+                }
+                return Self {
+                    signing: None,
+                    verifying: HashMap::new(),
+                    secret,
+                    jwks: JwkSet { keys: vec![] },
+                };
+            }
+        };
+
+        let mut verifying = HashMap::new();
+        let mut jwks = vec![];
+        let mut private_kids = vec![];
+
+        let entries = fs::read_dir(dir).expect("[-] 无法读取JWT密钥目录");
+        for entry in entries {
+            let path = entry.expect("[-] 无法读取JWT密钥目录").path();
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+
+            if let Some(kid) = name.strip_suffix(".pub.pem") {
+                let pem = fs::read(&path).expect("[-] 无法读取JWT公钥");
+                let (alg, key, jwk) = load_public_key(kid, &pem)
+                    .unwrap_or_else(|| panic!("[-] 不支持的JWT公钥: {}", name));
+                verifying.insert(kid.to_string(), (alg, key));
+                jwks.push(jwk);
+            } else if let Some(kid) = name.strip_suffix(".key.pem") {
+                private_kids.push(kid.to_string());
+            }
+        }
+
+        // 未指定时使用文件名排序最后的私钥，按日期命名即为最新的密钥
+        private_kids.sort();
+        let kid = match &config.jwt_signing_kid {
+            Some(kid) => kid.to_owned(),
+            None => private_kids
+                .pop()
+                .expect("[-] JWT密钥目录中没有私钥(*.key.pem)"),
+        };
+
+        let pem = fs::read(dir.join(format!("{}.key.pem", kid))).expect("[-] 无法读取JWT私钥");
+        let (alg, key) = if let Ok(key) = EncodingKey::from_rsa_pem(&pem) {
+            (Algorithm::RS256, key)
+        } else if let Ok(key) = EncodingKey::from_ed_pem(&pem) {
+            (Algorithm::EdDSA, key)
+        } else {
+            panic!("[-] 不支持的JWT私钥: {}", kid);
+        };
+
+        if !verifying.contains_key(&kid) {
+            panic!("[-] 缺少签名私钥对应的公钥: {}.pub.pem", kid);
+        }
+
+        // 共享密钥只用于验证迁移前签发的旧令牌，默认不接受
+        let secret = if config.jwt_accept_legacy_hs256 {
+            if secret.is_none() {
+                panic!("[-] 接受HS256旧令牌需要设置BOOKSTORE_JWT_SECRET");
+            }
+            warn!("已开启BOOKSTORE_JWT_ACCEPT_LEGACY_HS256，没有kid的HS256令牌仍可通过验证");
+            secret
+        } else {
+            None
+        };
+
+        Self {
+            signing: Some((kid, alg, key)),
+            verifying,
+            secret,
+            jwks: JwkSet { keys: jwks },
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, claims: &T) -> String {
+        match &self.signing {
+            Some((kid, alg, key)) => {
+                let mut header = Header::new(*alg);
+                header.kid = Some(kid.to_owned());
+                encode(&header, claims, key).unwrap()
+            }
+            None => encode(&Header::default(), claims, &self.secret.as_ref().unwrap().0).unwrap(),
+        }
+    }
+
+    // 按令牌头中的kid选择公钥，没有kid的令牌只在保留了共享密钥时验证
+    pub fn decode<T: DeserializeOwned>(&self, token: &str) -> Result<T, Error> {
+        let header = decode_header(token)?;
+
+        let (alg, key) = match &header.kid {
+            Some(kid) => match self.verifying.get(kid) {
+                Some((alg, key)) => (*alg, key),
+                None => return Err(ErrorKind::InvalidToken.into()),
+            },
+            None => match &self.secret {
+                Some((_, key)) => (Algorithm::HS256, key),
+                None => return Err(ErrorKind::InvalidToken.into()),
+            },
+        };
+
+        decode::<T>(token, key, &Validation::new(alg)).map(|data| data.claims)
+    }
+
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+}
+
+fn load_public_key(kid: &str, pem: &[u8]) -> Option<(Algorithm, DecodingKey, Jwk)> {
+    let common = |alg| CommonParameters {
+        public_key_use: Some(PublicKeyUse::Signature),
+        key_algorithm: Some(alg),
+        key_id: Some(kid.to_string()),
+        ..Default::default()
+    };
+
+    if let Ok(key) = DecodingKey::from_rsa_pem(pem) {
+        let text = std::str::from_utf8(pem).ok()?;
+        let public = RsaPublicKey::from_public_key_pem(text)
+            .or_else(|_| RsaPublicKey::from_pkcs1_pem(text))
+            .ok()?;
+
+        let jwk = Jwk {
+            common: common(KeyAlgorithm::RS256),
+            algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(public.n().to_bytes_be()),
+                e: URL_SAFE_NO_PAD.encode(public.e().to_bytes_be()),
+            }),
+        };
+        return Some((Algorithm::RS256, key, jwk));
+    }
+
+    if let Ok(key) = DecodingKey::from_ed_pem(pem) {
+        // Ed25519公钥的DER编码最后32字节即为原始公钥
+        let text = std::str::from_utf8(pem).ok()?;
+        let body = text
+            .lines()
+            .filter(|l| !l.starts_with("-----"))
+            .collect::<String>();
+        let der = STANDARD.decode(body).ok()?;
+        let raw = der.get(der.len().checked_sub(32)?..)?;
+
+        let jwk = Jwk {
+            common: common(KeyAlgorithm::EdDSA),
+            algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(raw),
+            }),
+        };
+        return Some((Algorithm::EdDSA, key, jwk));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{auth::generate_token, testing::IDP_PRIVATE_KEY};
+    use rocket::serde::json::{json, Value};
+
+    const PUBLIC_KEY: &str = "-----BEGIN PUBLIC KEY-----
+MCowBQYDK2VwAyEAGb9ECWmEzf6FQbrBZ9w7lshQhqowtrbLDFw4rXAxZuE=
+-----END PUBLIC KEY-----";
+
+    // 使用密钥目录的KeyStore，同时配置了共享密钥
+    fn key_store(accept_legacy_hs256: bool) -> KeyStore {
+        let dir = std::env::temp_dir().join(format!("bookstore-keys-{}", generate_token(8)));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("2024.key.pem"), IDP_PRIVATE_KEY).unwrap();
+        fs::write(dir.join("2024.pub.pem"), PUBLIC_KEY).unwrap();
+
+        let mut config = AppConfig::new();
+        config.jwt_sercert = Some("legacy-secret".to_string());
+        config.jwt_keys_dir = Some(dir.to_string_lossy().to_string());
+        config.jwt_signing_kid = None;
+        config.jwt_accept_legacy_hs256 = accept_legacy_hs256;
+        let keys = KeyStore::from_config(&config);
+
+        fs::remove_dir_all(&dir).ok();
+        keys
+    }
+
+    fn legacy_token() -> String {
+        let claims = json!({"sub": "1", "exp": u64::MAX / 2});
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(b"legacy-secret"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn tokens_without_kid_are_rejected_by_default() {
+        let keys = key_store(false);
+        assert!(keys.decode::<Value>(&legacy_token()).is_err());
+
+        let token = keys.encode(&json!({"sub": "1", "exp": u64::MAX / 2}));
+        assert!(keys.decode::<Value>(&token).is_ok());
+    }
+
+    #[test]
+    fn tokens_without_kid_are_accepted_when_enabled() {
+        let keys = key_store(true);
+        assert!(keys.decode::<Value>(&legacy_token()).is_ok());
+    }
+}
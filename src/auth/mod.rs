@@ -1,49 +1,583 @@
-use jsonwebtoken::{decode, DecodingKey, Validation};
+use std::{fmt, ops::Deref, str::FromStr, time::SystemTime};
+
+use jsonwebtoken::errors::ErrorKind;
+use rand::RngCore;
 use rocket::{
     http::Status,
     request::{self, FromRequest, Outcome, Request},
     serde::{Deserialize, Serialize},
 };
+use sea_orm::{prelude::DateTimeUtc, sea_query::Expr, *};
+use sha2::{Digest, Sha256};
+
+use crate::controllers::ErrorResponse;
+use crate::entities::{api_key, prelude::*, revoked_token, user};
+use crate::{AppConfig, UnverifiedPolicy};
+use audit::{ClientInfo, EventType};
+use keys::KeyStore;
+use throttle::{AuditThrottle, ClientAddr};
 
-use crate::AppConfig;
+pub mod audit;
+pub mod keys;
+pub mod oidc;
+pub mod password;
+pub mod throttle;
+pub mod totp;
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(crate = "rocket::serde")]
-struct Claims {
-    sub: i32,
-    role: String, // 角色
-    exp: u64,     // 过期时间
+pub struct Claims {
+    pub sub: i32,
+    pub role: String, // 角色
+    pub exp: u64,     // 过期时间
+    pub iat: u64,     // 签发时间
+    pub jti: String,  // 令牌ID，用于吊销
+    #[serde(default)]
+    pub sv: i32, // 会话版本，与用户当前版本不同时令牌失效
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>, // 代为操作的管理员（RFC 8693）
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Actor {
+    pub sub: i32,
+    #[serde(default)]
+    pub sv: i32, // 管理员签发令牌时的会话版本
+}
+
+// 生成随机的不透明令牌（十六进制）
+pub fn generate_token(bytes: usize) -> String {
+    let mut buf = vec![0u8; bytes];
+    rand::thread_rng().fill_bytes(&mut buf);
+    hex::encode(buf)
+}
+
+// 数据库中只保存令牌的sha256
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// 用户角色，按权限从低到高排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    ReadOnly,
+    User,
+    Editor,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::ReadOnly => "read-only",
+            Role::User => "user",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read-only" => Ok(Role::ReadOnly),
+            "user" => Ok(Role::User),
+            "editor" => Ok(Role::Editor),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!("Unknown role: {}", s)),
+        }
+    }
 }
 
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// 命令行创建管理员：系统中还没有管理员时，只能通过这种方式授予第一个管理员角色。
+// 返回false表示账号不存在
+pub async fn promote_admin(db: &DatabaseConnection, email: &str) -> Result<bool, DbErr> {
+    let u = match User::find()
+        .filter(user::Column::Email.eq(email))
+        .one(db)
+        .await?
+    {
+        Some(u) => u,
+        None => return Ok(false),
+    };
+
+    User::update_many()
+        .col_expr(user::Column::Role, Expr::value(Role::Admin.as_str()))
+        .filter(user::Column::Id.eq(u.id))
+        .exec(db)
+        .await?;
+
+    Ok(true)
+}
 
+#[derive(Clone)]
 pub struct AuthenticatedUser {
     pub id: i32,
+    pub role: Role,
+    pub jti: String,
+    pub exp: u64,
+    pub verified: bool,
+    pub api_key_id: Option<i32>,      // 通过API密钥认证时为密钥ID
+    pub scopes: Option<Vec<String>>,  // 为空表示不限制
+    pub impersonator_id: Option<i32>, // 管理员代为操作时为管理员ID
+}
+
+pub const API_KEY_PREFIX: &str = "bsk_";
+
+// API密钥可申请的权限范围
+pub const SCOPES: [&str; 4] = ["books:read", "books:write", "authors:read", "authors:write"];
+
+impl AuthenticatedUser {
+    // 编辑和管理员可以操作所有人的数据
+    pub fn is_elevated(&self) -> bool {
+        self.role >= Role::Editor
+    }
+
+    // 检查API密钥的权限范围，写权限包含读权限
+    pub fn ensure_scope(&self, scope: &str) -> Result<(), ErrorResponse> {
+        let scopes = match &self.scopes {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+
+        let write = scope.replace(":read", ":write");
+        if scopes.iter().any(|s| s == scope || *s == write) {
+            Ok(())
+        } else {
+            Err(ErrorResponse((
+                Status::Forbidden,
+                format!("The API key is missing the {} scope.", scope),
+            )))
+        }
+    }
+
+    // 检查当前用户是否为数据的所有者，否则返回403
+    pub fn ensure_owner(&self, owner_id: i32) -> Result<(), ErrorResponse> {
+        if self.id == owner_id || self.is_elevated() {
+            Ok(())
+        } else {
+            Err(ErrorResponse((
+                Status::Forbidden,
+                "You do not have permission to modify this resource.".to_string(),
+            )))
+        }
+    }
 }
 
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for AuthenticatedUser {
     type Error = String;
 
-    async fn from_request(req: &'r Request<'_>)-> request::Outcome<Self, Self::Error> {
-        if let Some(token) = req.headers().get_one("Token") {
-            let config = req.rocket().state::<AppConfig>().unwrap();
-
-            let data = decode::<Claims>(
-                token, 
-                &DecodingKey::from_secret(config.jwt_sercert.as_bytes()), 
-                &Validation::new(jsonwebtoken::Algorithm::HS256),
-            );
-
-            let claims = match data {
-                Ok(p) => p.claims,
-                Err(_) => {
-                    return Outcome::Error((Status::Unauthorized, "Invalid token".to_string()));
-                }
+    // 其他守卫也会读取AuthenticatedUser，认证结果缓存在请求中，
+    // 同一请求只查询数据库和写入审计日志一次
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        req.local_cache_async(authenticate(req)).await.clone()
+    }
+}
+
+async fn authenticate(req: &Request<'_>) -> request::Outcome<AuthenticatedUser, String> {
+    let token = match bearer_token(req) {
+        Some(t) => t,
+        None => return fail(req, Status::Unauthorized, None, "Token absent"),
+    };
+
+    let db = req.rocket().state::<DatabaseConnection>().unwrap();
+
+    if token.starts_with(API_KEY_PREFIX) {
+        return match authenticate_api_key(db, token).await {
+            Ok(Some(user)) => Outcome::Success(user),
+            Ok(None) => {
+                let desc = "The API key is invalid, expired or revoked";
+                audit_token_error(req, None, desc).await;
+                fail(req, Status::Unauthorized, Some("invalid_token"), desc)
+            }
+            Err(err) => Outcome::Error((Status::InternalServerError, err.to_string())),
+        };
+    }
+
+    let keys = req.rocket().state::<KeyStore>().unwrap();
+
+    let claims = match keys.decode::<Claims>(token) {
+        Ok(c) => c,
+        Err(e) => {
+            let desc = match e.kind() {
+                ErrorKind::ExpiredSignature => "The access token expired",
+                _ => "The access token is invalid",
             };
+            audit_token_error(req, None, desc).await;
+            return fail(req, Status::Unauthorized, Some("invalid_token"), desc);
+        }
+    };
+
+    let u = match load_user(db, &claims).await {
+        Ok(Some(u)) => u,
+        Ok(None) => {
+            let desc = "The access token has been revoked";
+            audit_token_error(req, Some(claims.sub), desc).await;
+            return fail(req, Status::Unauthorized, Some("invalid_token"), desc);
+        }
+        Err(err) => return Outcome::Error((Status::InternalServerError, err.to_string())),
+    };
+
+    if u.disabled_at.is_some() {
+        let desc = "The account has been disabled";
+        audit_token_error(req, Some(u.id), desc).await;
+        return fail(req, Status::Unauthorized, Some("invalid_token"), desc);
+    }
+
+    // 角色以数据库为准，令牌中的角色可能已经过时
+    let role = match u.role.parse::<Role>() {
+        Ok(r) => r,
+        Err(_) => {
+            let desc = "The access token is invalid";
+            audit_token_error(req, Some(u.id), desc).await;
+            return fail(req, Status::Unauthorized, Some("invalid_token"), desc);
+        }
+    };
 
-            Outcome::Success(AuthenticatedUser {id: claims.sub})
-        }else {
-            Outcome::Error((Status::Unauthorized, "Token absent".to_string()))
+    if let Some(act) = &claims.act {
+        // 管理员被停用、降级或退出所有会话后，代为操作的令牌随之失效
+        match actor_is_valid(db, act).await {
+            Ok(true) => {}
+            Ok(false) => {
+                let desc = "The impersonation session is no longer valid";
+                audit_token_error(req, Some(u.id), desc).await;
+                return fail(req, Status::Unauthorized, Some("invalid_token"), desc);
+            }
+            Err(err) => return Outcome::Error((Status::InternalServerError, err.to_string())),
         }
+        audit_impersonation(req, u.id, act.sub).await;
     }
-}
\ No newline at end of file
+
+    Outcome::Success(AuthenticatedUser {
+        id: claims.sub,
+        role,
+        jti: claims.jti,
+        exp: claims.exp,
+        verified: u.verified_at.is_some(),
+        api_key_id: None,
+        scopes: None,
+        impersonator_id: claims.act.map(|a| a.sub),
+    })
+}
+
+async fn authenticate_api_key(
+    db: &DatabaseConnection,
+    token: &str,
+) -> Result<Option<AuthenticatedUser>, DbErr> {
+    let now = DateTimeUtc::from(SystemTime::now());
+
+    let key = match ApiKey::find()
+        .filter(api_key::Column::KeyHash.eq(hash_token(token)))
+        .filter(api_key::Column::RevokedAt.is_null())
+        .one(db)
+        .await?
+    {
+        Some(k) => k,
+        None => return Ok(None),
+    };
+
+    if matches!(key.expires_at, Some(t) if t <= now) {
+        return Ok(None);
+    }
+
+    let u = match User::find_by_id(key.user_id).one(db).await? {
+        Some(u) if u.disabled_at.is_none() => u,
+        _ => return Ok(None),
+    };
+
+    let role = match u.role.parse::<Role>() {
+        Ok(r) => r,
+        Err(_) => return Ok(None),
+    };
+
+    ApiKey::update_many()
+        .col_expr(api_key::Column::LastUsedAt, Expr::value(now))
+        .filter(api_key::Column::Id.eq(key.id))
+        .exec(db)
+        .await?;
+
+    Ok(Some(AuthenticatedUser {
+        id: u.id,
+        role,
+        jti: String::new(),
+        exp: 0,
+        verified: u.verified_at.is_some(),
+        api_key_id: Some(key.id),
+        scopes: key
+            .scopes
+            .map(|s| s.split(',').map(|s| s.to_string()).collect()),
+        impersonator_id: None,
+    }))
+}
+
+// 优先读取标准的Authorization: Bearer头，兼容旧的Token头
+fn bearer_token<'r>(req: &'r Request<'_>) -> Option<&'r str> {
+    if let Some(value) = req.headers().get_one("Authorization") {
+        let (scheme, token) = value.split_once(' ')?;
+        if scheme.eq_ignore_ascii_case("Bearer") {
+            return Some(token.trim());
+        }
+    }
+
+    req.headers().get_one("Token")
+}
+
+// 认证失败的原因，供catcher生成WWW-Authenticate头（RFC 6750）
+#[derive(Default)]
+pub struct AuthFailure {
+    pub error: Option<&'static str>,
+    pub description: String,
+}
+
+fn fail<T>(
+    req: &Request<'_>,
+    status: Status,
+    error: Option<&'static str>,
+    description: &str,
+) -> request::Outcome<T, String> {
+    req.local_cache(|| AuthFailure {
+        error,
+        description: description.to_string(),
+    });
+
+    Outcome::Error((status, description.to_string()))
+}
+
+// 记录令牌错误，审计日志写入失败不影响请求本身。
+// 任何人都能发送无效令牌，每个IP在窗口内只记录有限的条数
+async fn audit_token_error(req: &Request<'_>, user_id: Option<i32>, description: &str) {
+    let db = req.rocket().state::<DatabaseConnection>().unwrap();
+    let throttle = req.rocket().state::<AuditThrottle>().unwrap();
+
+    if let Outcome::Success(client) = req.guard::<ClientAddr>().await {
+        if !throttle.allow(client.ip) {
+            return;
+        }
+    }
+
+    if let Outcome::Success(info) = req.guard::<ClientInfo>().await {
+        info.record(
+            db,
+            user_id,
+            EventType::TokenInvalid,
+            Some(description.to_string()),
+        )
+        .await;
+    }
+}
+
+// 代为操作期间的每个请求都写入审计日志
+async fn audit_impersonation(req: &Request<'_>, user_id: i32, admin_id: i32) {
+    let db = req.rocket().state::<DatabaseConnection>().unwrap();
+
+    if let Outcome::Success(info) = req.guard::<ClientInfo>().await {
+        info.record(
+            db,
+            Some(user_id),
+            EventType::Impersonation,
+            Some(format!(
+                "{} {} by admin {}",
+                req.method(),
+                req.uri(),
+                admin_id
+            )),
+        )
+        .await;
+    }
+}
+
+// 令牌在黑名单中，或签发于用户退出所有会话之前时返回None
+async fn load_user(db: &DatabaseConnection, claims: &Claims) -> Result<Option<user::Model>, DbErr> {
+    if RevokedToken::find()
+        .filter(revoked_token::Column::Jti.eq(&claims.jti))
+        .one(db)
+        .await?
+        .is_some()
+    {
+        return Ok(None);
+    }
+
+    let u = match User::find_by_id(claims.sub).one(db).await? {
+        Some(u) => u,
+        None => return Ok(None),
+    };
+
+    if claims.sv != u.session_version {
+        return Ok(None);
+    }
+
+    Ok(Some(u))
+}
+
+// 代为操作的管理员必须仍然可用、仍是管理员，且会话版本与签发时相同
+async fn actor_is_valid(db: &DatabaseConnection, act: &Actor) -> Result<bool, DbErr> {
+    let admin = match User::find_by_id(act.sub).one(db).await? {
+        Some(u) => u,
+        None => return Ok(false),
+    };
+
+    Ok(admin.disabled_at.is_none()
+        && admin.role.parse::<Role>() == Ok(Role::Admin)
+        && admin.session_version == act.sv)
+}
+
+// 要求最低角色的请求守卫，通过Deref访问AuthenticatedUser
+async fn require_role(req: &Request<'_>, min: Role) -> request::Outcome<AuthenticatedUser, String> {
+    let config = req.rocket().state::<AppConfig>().unwrap();
+
+    match req.guard::<AuthenticatedUser>().await {
+        // 未验证邮箱的用户不能写入数据
+        Outcome::Success(user)
+            if !user.verified && config.unverified_policy == UnverifiedPolicy::BlockWrites =>
+        {
+            fail(
+                req,
+                Status::Forbidden,
+                Some("insufficient_scope"),
+                "Please verify your email address",
+            )
+        }
+        Outcome::Success(user) if user.role >= min => Outcome::Success(user),
+        Outcome::Success(_) => fail(
+            req,
+            Status::Forbidden,
+            Some("insufficient_scope"),
+            "Insufficient permissions",
+        ),
+        Outcome::Error(e) => Outcome::Error(e),
+        Outcome::Forward(s) => Outcome::Forward(s),
+    }
+}
+
+macro_rules! role_guard {
+    ($(#[$meta:meta])* $name:ident, $role:expr) => {
+        $(#[$meta])*
+        pub struct $name(pub AuthenticatedUser);
+
+        impl Deref for $name {
+            type Target = AuthenticatedUser;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        #[rocket::async_trait]
+        impl<'r> FromRequest<'r> for $name {
+            type Error = String;
+
+            async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+                require_role(req, $role).await.map($name)
+            }
+        }
+    };
+}
+
+// 可以创建数据的用户（非只读）
+role_guard!(WriterUser, Role::User);
+// 可以管理分类的用户
+role_guard!(EditorUser, Role::Editor);
+
+// 管理员接口只接受登录会话的令牌，API密钥的权限范围不包含管理操作
+pub struct AdminUser(pub AuthenticatedUser);
+
+impl Deref for AdminUser {
+    type Target = AuthenticatedUser;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminUser {
+    type Error = String;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        match require_role(req, Role::Admin).await {
+            Outcome::Success(user) if user.api_key_id.is_some() => fail(
+                req,
+                Status::Forbidden,
+                Some("insufficient_scope"),
+                "Admin actions cannot be performed with an API key",
+            ),
+            outcome => outcome.map(AdminUser),
+        }
+    }
+}
+
+// 只接受用户本人登录会话的令牌，API密钥和管理员代为操作时不能管理账号
+pub struct SessionUser(pub AuthenticatedUser);
+
+impl Deref for SessionUser {
+    type Target = AuthenticatedUser;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SessionUser {
+    type Error = String;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        match req.guard::<AuthenticatedUser>().await {
+            Outcome::Success(user) if user.api_key_id.is_some() => fail(
+                req,
+                Status::Forbidden,
+                Some("insufficient_scope"),
+                "This action requires a signed-in session",
+            ),
+            Outcome::Success(user) if user.impersonator_id.is_some() => fail(
+                req,
+                Status::Forbidden,
+                Some("insufficient_scope"),
+                "This action is not allowed while impersonating a user",
+            ),
+            Outcome::Success(user) => Outcome::Success(SessionUser(user)),
+            Outcome::Error(e) => Outcome::Error(e),
+            Outcome::Forward(s) => Outcome::Forward(s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{entities::auth_event, testing};
+    use rocket::http::Header;
+
+    #[rocket::async_test]
+    async fn token_errors_are_audited_a_limited_number_of_times_per_ip() {
+        let app = testing::app(|config| config.audit_max_token_errors = 3).await;
+
+        for _ in 0..10 {
+            let res = app
+                .client
+                .get("/auth/me")
+                .remote("127.0.0.1:8000".parse().unwrap())
+                .header(Header::new("Authorization", "Bearer not-a-token"))
+                .dispatch()
+                .await;
+            assert_eq!(res.status(), Status::Unauthorized);
+        }
+
+        let events = AuthEvent::find()
+            .filter(auth_event::Column::EventType.eq(EventType::TokenInvalid.as_str()))
+            .count(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(events, 3);
+    }
+}
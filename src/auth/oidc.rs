@@ -0,0 +1,364 @@
+use std::time::SystemTime;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+use reqwest::Url;
+use rocket::serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{generate_token, keys::KeyStore};
+use crate::AppConfig;
+
+// OpenID Connect身份提供方（授权码 + PKCE）
+pub struct OidcProvider {
+    pub issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_url: String,
+    scopes: String,
+    http: reqwest::Client,
+}
+
+// 提供方的/.well-known/openid-configuration
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct Discovery {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+}
+
+// 登录流程的状态，签名后保存在浏览器Cookie中
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct FlowState {
+    pub state: String,
+    pub nonce: String,
+    pub verifier: String, // PKCE code_verifier
+    purpose: String,
+    exp: u64,
+}
+
+const FLOW_PURPOSE: &str = "oidc";
+
+// ID令牌只接受非对称签名算法
+const ALLOWED_ALGORITHMS: [Algorithm; 9] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+// 签名算法由提供方的公钥决定，不能信任令牌头中的alg。
+// 公钥没有声明alg时按密钥类型取默认算法
+fn jwk_algorithm(jwk: &Jwk) -> Option<Algorithm> {
+    let alg = match jwk.common.key_algorithm {
+        Some(alg) => alg.to_string().parse().ok()?,
+        None => match &jwk.algorithm {
+            AlgorithmParameters::RSA(_) => Algorithm::RS256,
+            AlgorithmParameters::EllipticCurve(p) if p.curve == EllipticCurve::P384 => {
+                Algorithm::ES384
+            }
+            AlgorithmParameters::EllipticCurve(_) => Algorithm::ES256,
+            AlgorithmParameters::OctetKeyPair(_) => Algorithm::EdDSA,
+            AlgorithmParameters::OctetKey(_) => return None,
+        },
+    };
+
+    ALLOWED_ALGORITHMS.contains(&alg).then_some(alg)
+}
+
+impl FlowState {
+    pub fn new(ttl: u64) -> Self {
+        Self {
+            state: generate_token(16),
+            nonce: generate_token(16),
+            verifier: generate_token(32),
+            purpose: FLOW_PURPOSE.to_string(),
+            exp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+                + ttl,
+        }
+    }
+
+    pub fn encode(&self, keys: &KeyStore) -> String {
+        keys.encode(self)
+    }
+
+    pub fn decode(keys: &KeyStore, token: &str) -> Option<Self> {
+        let flow = keys.decode::<FlowState>(token).ok()?;
+
+        if flow.purpose != FLOW_PURPOSE {
+            return None;
+        }
+
+        Some(flow)
+    }
+
+    // S256: BASE64URL(SHA256(code_verifier))
+    fn challenge(&self) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(self.verifier.as_bytes()))
+    }
+}
+
+impl OidcProvider {
+    // 未配置BOOKSTORE_OIDC_ISSUER时不启用
+    pub fn from_config(config: &AppConfig) -> Option<Self> {
+        let issuer = config.oidc_issuer.to_owned()?;
+
+        Some(Self {
+            issuer,
+            client_id: config
+                .oidc_client_id
+                .to_owned()
+                .expect("[-] 请设置BOOKSTORE_OIDC_CLIENT_ID"),
+            client_secret: config.oidc_client_secret.to_owned(),
+            redirect_url: config
+                .oidc_redirect_url
+                .to_owned()
+                .unwrap_or(format!("{}/auth/oidc/callback", config.app_url)),
+            scopes: config.oidc_scopes.to_owned(),
+            http: reqwest::Client::new(),
+        })
+    }
+
+    // 每次都重新读取，身份提供方的端点和密钥轮换后无需重启
+    async fn discover(&self) -> Result<Discovery, String> {
+        self.http
+            .get(format!(
+                "{}/.well-known/openid-configuration",
+                self.issuer.trim_end_matches('/')
+            ))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("OIDC discovery failed: {}", e))?
+            .json::<Discovery>()
+            .await
+            .map_err(|e| format!("OIDC discovery failed: {}", e))
+    }
+
+    pub async fn authorize_url(&self, flow: &FlowState) -> Result<String, String> {
+        let discovery = self.discover().await?;
+
+        let url = Url::parse_with_params(
+            &discovery.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", &self.client_id),
+                ("redirect_uri", &self.redirect_url),
+                ("scope", &self.scopes),
+                ("state", &flow.state),
+                ("nonce", &flow.nonce),
+                ("code_challenge", &flow.challenge()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| format!("Invalid authorization endpoint: {}", e))?;
+
+        Ok(url.to_string())
+    }
+
+    // 用授权码换取ID令牌
+    pub async fn exchange(&self, code: &str, flow: &FlowState) -> Result<IdTokenClaims, String> {
+        let discovery = self.discover().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.redirect_url),
+            ("client_id", &self.client_id),
+            ("code_verifier", &flow.verifier),
+        ];
+        if let Some(secret) = &self.client_secret {
+            form.push(("client_secret", secret));
+        }
+
+        let token = self
+            .http
+            .post(&discovery.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("OIDC token exchange failed: {}", e))?
+            .json::<TokenResponse>()
+            .await
+            .map_err(|e| format!("OIDC token exchange failed: {}", e))?;
+
+        let jwks = self
+            .http
+            .get(&discovery.jwks_uri)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Failed to fetch the OIDC JWKS: {}", e))?
+            .json::<JwkSet>()
+            .await
+            .map_err(|e| format!("Failed to fetch the OIDC JWKS: {}", e))?;
+
+        self.verify(&jwks, &token.id_token, &flow.nonce)
+    }
+
+    // 校验ID令牌的签名、签发方、受众和nonce
+    fn verify(&self, jwks: &JwkSet, id_token: &str, nonce: &str) -> Result<IdTokenClaims, String> {
+        let header = decode_header(id_token).map_err(|e| e.to_string())?;
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None => jwks.keys.first(),
+        }
+        .ok_or("No matching key for the ID token".to_string())?;
+        let alg = jwk_algorithm(jwk).ok_or("Unsupported ID token signing key".to_string())?;
+        let key = DecodingKey::from_jwk(jwk).map_err(|e| e.to_string())?;
+
+        let mut validation = Validation::new(alg);
+        validation.set_audience(&[&self.client_id]);
+        validation.set_issuer(&[&self.issuer]);
+
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map_err(|e| format!("Invalid ID token: {}", e))?
+            .claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err("Invalid ID token: nonce mismatch".to_string());
+        }
+
+        Ok(claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{IDP_PRIVATE_KEY as PRIVATE_KEY, IDP_PUBLIC_X as PUBLIC_X};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use rocket::serde::json::{from_str, json, Value};
+
+    fn provider() -> OidcProvider {
+        OidcProvider {
+            issuer: "https://idp.example.com".to_string(),
+            client_id: "bookstore".to_string(),
+            client_secret: None,
+            redirect_url: "https://bookstore.example.com/auth/oidc/callback".to_string(),
+            scopes: "openid email".to_string(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    fn jwks(alg: Option<&str>) -> JwkSet {
+        let mut jwk = json!({"kty": "OKP", "crv": "Ed25519", "kid": "k1", "x": PUBLIC_X});
+        if let Some(alg) = alg {
+            jwk["alg"] = json!(alg);
+        }
+        from_str(&json!({ "keys": [jwk] }).to_string()).unwrap()
+    }
+
+    fn claims(nonce: &str, aud: &str) -> Value {
+        let exp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 300;
+        json!({
+            "iss": "https://idp.example.com",
+            "aud": aud,
+            "sub": "user-1",
+            "exp": exp,
+            "nonce": nonce,
+            "email": "reader@example.com",
+        })
+    }
+
+    fn sign(claims: &Value) -> String {
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some("k1".to_string());
+        let key = EncodingKey::from_ed_pem(PRIVATE_KEY.as_bytes()).unwrap();
+        encode(&header, claims, &key).unwrap()
+    }
+
+    #[test]
+    fn accepts_a_valid_id_token() {
+        let token = sign(&claims("n1", "bookstore"));
+
+        let claims = provider()
+            .verify(&jwks(Some("EdDSA")), &token, "n1")
+            .unwrap();
+        assert_eq!(claims.sub, "user-1");
+        assert_eq!(claims.email.as_deref(), Some("reader@example.com"));
+    }
+
+    #[test]
+    fn infers_the_algorithm_from_the_key_type() {
+        let token = sign(&claims("n1", "bookstore"));
+
+        assert!(provider().verify(&jwks(None), &token, "n1").is_ok());
+    }
+
+    #[test]
+    fn rejects_an_algorithm_chosen_by_the_token_header() {
+        // 用公钥作为HMAC密钥伪造的令牌
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("k1".to_string());
+        let key = EncodingKey::from_secret(PUBLIC_X.as_bytes());
+        let token = encode(&header, &claims("n1", "bookstore"), &key).unwrap();
+
+        assert!(provider()
+            .verify(&jwks(Some("EdDSA")), &token, "n1")
+            .is_err());
+        assert!(provider().verify(&jwks(None), &token, "n1").is_err());
+    }
+
+    #[test]
+    fn rejects_symmetric_keys() {
+        let jwks: JwkSet = from_str(
+            &json!({"keys": [{"kty": "oct", "kid": "k1", "alg": "HS256", "k": "c2VjcmV0"}]})
+                .to_string(),
+        )
+        .unwrap();
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("k1".to_string());
+        let key = EncodingKey::from_secret(b"secret");
+        let token = encode(&header, &claims("n1", "bookstore"), &key).unwrap();
+
+        assert!(provider().verify(&jwks, &token, "n1").is_err());
+    }
+
+    #[test]
+    fn rejects_a_wrong_nonce_or_audience() {
+        let jwks = jwks(Some("EdDSA"));
+
+        let token = sign(&claims("n1", "bookstore"));
+        assert!(provider().verify(&jwks, &token, "n2").is_err());
+
+        let token = sign(&claims("n1", "another-client"));
+        assert!(provider().verify(&jwks, &token, "n1").is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use std::{collections::HashSet, fs};
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use rocket::http::Status;
+
+use crate::controllers::ErrorResponse;
+use crate::AppConfig;
+
+// 密码策略：长度限制和本地的泄露密码列表
+pub struct PasswordPolicy {
+    min_length: usize,
+    max_length: usize,
+    breached: HashSet<String>,
+}
+
+impl PasswordPolicy {
+    pub fn from_config(config: &AppConfig) -> Self {
+        // 每行一个密码，忽略大小写
+        let breached = match &config.breached_passwords_file {
+            Some(path) => fs::read_to_string(path)
+                .expect("[-] 无法读取泄露密码列表")
+                .lines()
+                .map(|l| l.trim().to_lowercase())
+                .filter(|l| !l.is_empty())
+                .collect(),
+            None => HashSet::new(),
+        };
+
+        Self {
+            min_length: config.password_min_length,
+            max_length: config.password_max_length,
+            breached,
+        }
+    }
+
+    pub fn check(&self, password: &str) -> Result<(), ErrorResponse> {
+        let len = password.chars().count();
+
+        let msg = if len < self.min_length {
+            format!(
+                "The password must be at least {} characters long.",
+                self.min_length
+            )
+        } else if len > self.max_length {
+            format!(
+                "The password must be at most {} characters long.",
+                self.max_length
+            )
+        } else if self.breached.contains(&password.to_lowercase()) {
+            "This password has appeared in a data breach, please choose another one.".to_string()
+        } else {
+            return Ok(());
+        };
+
+        Err(ErrorResponse((Status::UnprocessableEntity, msg)))
+    }
+}
+
+// 使用Argon2id生成密码哈希
+pub fn hash_password(password: &str) -> Result<String, ErrorResponse> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| ErrorResponse((Status::InternalServerError, e.to_string())))
+}
+
+// 同时支持Argon2和旧的bcrypt哈希，哈希格式错误时视为不匹配
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    if hash.starts_with("$argon2") {
+        match PasswordHash::new(hash) {
+            Ok(parsed) => Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok(),
+            Err(_) => false,
+        }
+    } else {
+        bcrypt::verify(password, hash).unwrap_or(false)
+    }
+}
+
+// 不是Argon2id的哈希在登录成功后重新计算
+pub fn needs_rehash(hash: &str) -> bool {
+    !hash.starts_with("$argon2id$")
+}
@@ -0,0 +1,122 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rocket::{
+    http::Status,
+    request::{self, FromRequest, Outcome, Request},
+};
+use rocket_client_addr::ClientRealAddr;
+
+use crate::AppConfig;
+
+// 客户端IP。请求头可以被客户端伪造，只有部署在可信代理之后时才从请求头读取，
+// 否则使用连接的对端地址。
+pub struct ClientAddr {
+    pub ip: IpAddr,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientAddr {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let config = req.rocket().state::<AppConfig>().unwrap();
+
+        let ip = if config.trusted_proxy {
+            match req.guard::<&ClientRealAddr>().await {
+                Outcome::Success(client) => Some(client.ip),
+                _ => None,
+            }
+        } else {
+            req.remote().map(|addr| addr.ip())
+        };
+
+        match ip {
+            Some(ip) => Outcome::Success(ClientAddr { ip }),
+            None => Outcome::Error((Status::BadRequest, ())),
+        }
+    }
+}
+
+// 按键统计失败次数（滑动窗口），超过上限后拒绝
+pub struct Throttle<K> {
+    failures: Mutex<HashMap<K, Vec<Instant>>>,
+    max_failures: usize,
+    window: Duration,
+}
+
+// 按客户端IP统计登录失败次数
+pub type LoginThrottle = Throttle<IpAddr>;
+
+// 按邮箱统计重置密码请求，防止向同一邮箱大量发送邮件
+pub type ResetThrottle = Throttle<String>;
+
+impl<K: Eq + Hash> Throttle<K> {
+    pub fn new(max_failures: usize, window_secs: u64) -> Self {
+        Self {
+            failures: Mutex::new(HashMap::new()),
+            max_failures,
+            window: Duration::from_secs(window_secs),
+        }
+    }
+
+    // 被限流时返回需要等待的秒数
+    pub fn check(&self, key: &K) -> Result<(), u64> {
+        let mut failures = self.failures.lock().unwrap();
+        let now = Instant::now();
+
+        let entries = match failures.get_mut(key) {
+            Some(e) => e,
+            None => return Ok(()),
+        };
+        entries.retain(|t| now.duration_since(*t) < self.window);
+
+        if entries.len() < self.max_failures {
+            if entries.is_empty() {
+                failures.remove(key);
+            }
+            return Ok(());
+        }
+
+        let oldest = entries[0];
+        Err((self.window - now.duration_since(oldest)).as_secs() + 1)
+    }
+
+    pub fn record_failure(&self, key: K) {
+        let mut failures = self.failures.lock().unwrap();
+        let now = Instant::now();
+
+        // 顺便清理窗口外的记录，防止内存无限增长
+        failures.retain(|_, entries| {
+            entries.retain(|t| now.duration_since(*t) < self.window);
+            !entries.is_empty()
+        });
+
+        failures.entry(key).or_default().push(now);
+    }
+}
+
+// 令牌错误审计记录的限流：每个IP在窗口内最多记录max_events条，
+// 防止用无效令牌写满审计表
+pub struct AuditThrottle(LoginThrottle);
+
+impl AuditThrottle {
+    pub fn new(max_events: usize, window_secs: u64) -> Self {
+        Self(LoginThrottle::new(max_events, window_secs))
+    }
+
+    // 未超过上限时计入一条并返回true
+    pub fn allow(&self, ip: IpAddr) -> bool {
+        if self.0.check(&ip).is_err() {
+            return false;
+        }
+        self.0.record_failure(ip);
+
+        true
+    }
+}
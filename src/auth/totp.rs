@@ -0,0 +1,121 @@
+use std::time::SystemTime;
+
+use rocket::serde::{Deserialize, Serialize};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use super::{generate_token, keys::KeyStore};
+use crate::AppConfig;
+
+// 两步验证的临时令牌，只能用来换取正式的访问令牌
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ChallengeClaims {
+    sub: i32,
+    jti: String, // 验证成功后写入吊销表，令牌只能使用一次
+    purpose: String,
+    exp: u64,
+}
+
+// 通过校验的临时令牌
+pub struct Challenge {
+    pub user_id: i32,
+    pub jti: String,
+    pub exp: u64,
+}
+
+const CHALLENGE_PURPOSE: &str = "2fa";
+
+// 生成新的TOTP密钥（base32编码）
+pub fn generate_secret() -> String {
+    Secret::generate_secret().to_encoded().to_string()
+}
+
+fn build(secret: &str, issuer: &str, account: &str) -> Option<TOTP> {
+    let bytes = Secret::Encoded(secret.to_owned()).to_bytes().ok()?;
+
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        0, // 时间误差在check_code中处理
+        30,
+        bytes,
+        Some(issuer.to_owned()),
+        account.to_owned(),
+    )
+    .ok()
+}
+
+// 供身份验证器App扫码使用的otpauth://链接
+pub fn otpauth_uri(secret: &str, issuer: &str, account: &str) -> Option<String> {
+    build(secret, issuer, account).map(|totp| totp.get_url())
+}
+
+// 校验动态码，允许前后各一个时间步的误差，返回匹配的时间步。
+// 不晚于last_step的时间步已经使用过，不再接受，防止动态码被重放
+pub fn check_code(secret: &str, code: &str, last_step: Option<i64>) -> Option<i64> {
+    let totp = build(secret, "BookStore", "user")?;
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let current = (now / totp.step) as i64;
+
+    (current - 1..=current + 1)
+        .filter(|step| last_step.is_none_or(|last| *step > last))
+        .find(|step| totp.check(code.trim(), *step as u64 * totp.step))
+}
+
+pub fn encode_challenge(config: &AppConfig, keys: &KeyStore, user_id: i32) -> String {
+    let claims = ChallengeClaims {
+        sub: user_id,
+        jti: generate_token(16),
+        purpose: CHALLENGE_PURPOSE.to_string(),
+        exp: SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + config.challenge_token_ttl,
+    };
+
+    keys.encode(&claims)
+}
+
+// 校验临时令牌的签名、用途和有效期
+pub fn decode_challenge(keys: &KeyStore, token: &str) -> Option<Challenge> {
+    let claims = keys.decode::<ChallengeClaims>(token).ok()?;
+
+    if claims.purpose != CHALLENGE_PURPOSE {
+        return None;
+    }
+
+    Some(Challenge {
+        user_id: claims.sub,
+        jti: claims.jti,
+        exp: claims.exp,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_a_code_from_an_already_used_step() {
+        let secret = generate_secret();
+        let code = build(&secret, "BookStore", "user")
+            .unwrap()
+            .generate_current()
+            .unwrap();
+
+        let step = check_code(&secret, &code, None).unwrap();
+        assert_eq!(check_code(&secret, &code, Some(step - 1)), Some(step));
+        assert_eq!(check_code(&secret, &code, Some(step)), None);
+    }
+
+    #[test]
+    fn rejects_a_wrong_code() {
+        let secret = generate_secret();
+
+        assert_eq!(check_code(&secret, "abcdef", None), None);
+    }
+}
@@ -0,0 +1,40 @@
+use rocket::{
+    http::{Header, Status},
+    Request,
+};
+
+use crate::auth::AuthFailure;
+
+#[derive(Responder)]
+pub struct Challenge(pub (Status, String), pub Header<'static>);
+
+// 按RFC 6750生成WWW-Authenticate头
+fn challenge(req: &Request<'_>, status: Status) -> Challenge {
+    let failure = req.local_cache(AuthFailure::default);
+
+    let mut value = r#"Bearer realm="bookstore""#.to_string();
+    if let Some(error) = failure.error {
+        value.push_str(&format!(
+            r#", error="{}", error_description="{}""#,
+            error, failure.description
+        ));
+    }
+
+    let body = if failure.description.is_empty() {
+        status.reason_lossy().to_string()
+    } else {
+        failure.description.to_owned()
+    };
+
+    Challenge((status, body), Header::new("WWW-Authenticate", value))
+}
+
+#[catch(401)]
+pub fn unauthorized(req: &Request) -> Challenge {
+    challenge(req, Status::Unauthorized)
+}
+
+#[catch(403)]
+pub fn forbidden(req: &Request) -> Challenge {
+    challenge(req, Status::Forbidden)
+}
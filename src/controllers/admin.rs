@@ -0,0 +1,548 @@
+use rocket::{
+    http::Status,
+    serde::{json::Json, Deserialize, Serialize},
+    State,
+};
+use sea_orm::{prelude::DateTimeUtc, sea_query::Expr, *};
+use std::time::SystemTime;
+
+use super::{
+    auth::{revoke_all_sessions, send_password_reset, sign_access_token},
+    pagination::{check_page, escape_like},
+    ErrorResponse, Response, SuccessResponse,
+};
+use crate::auth::{
+    audit::{ClientInfo, EventType},
+    generate_token,
+    keys::KeyStore,
+    password::hash_password,
+    Actor, AdminUser, Role,
+};
+use crate::entities::{auth_event, prelude::*, user};
+use crate::mailer::Mailer;
+use crate::search::SearchIndex;
+use crate::AppConfig;
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResUser {
+    id: i32,
+    email: String,
+    firstname: Option<String>,
+    lastname: Option<String>,
+    role: String,
+    verified: bool,
+    two_factor_enabled: bool,
+    locked_until: Option<DateTimeUtc>,
+    disabled_at: Option<DateTimeUtc>,
+    created_at: Option<DateTimeUtc>,
+}
+
+impl From<&user::Model> for ResUser {
+    fn from(value: &user::Model) -> Self {
+        Self {
+            id: value.id,
+            email: value.email.to_owned(),
+            firstname: value.firstname.to_owned(),
+            lastname: value.lastname.to_owned(),
+            role: value.role.to_owned(),
+            verified: value.verified_at.is_some(),
+            two_factor_enabled: value.totp_enabled_at.is_some(),
+            locked_until: value.locked_until,
+            disabled_at: value.disabled_at,
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResUserList {
+    total: u64,
+    page: u64,
+    per_page: u64,
+    users: Vec<ResUser>,
+}
+
+async fn find_user(db: &DatabaseConnection, id: i32) -> Result<user::Model, ErrorResponse> {
+    match User::find_by_id(id).one(db).await? {
+        Some(u) => Ok(u),
+        None => Err(ErrorResponse((
+            Status::NotFound,
+            "No user with the specified ID.".to_string(),
+        ))),
+    }
+}
+
+// 按邮箱或姓名搜索，分页返回
+#[get("/users?<q>&<page>&<per_page>")]
+pub async fn users(
+    db: &State<DatabaseConnection>,
+    _admin: AdminUser,
+    q: Option<String>,
+    page: Option<u64>,
+    per_page: Option<u64>,
+) -> Response<Json<ResUserList>> {
+    let db = db as &DatabaseConnection;
+    let page = page.unwrap_or(1).max(1);
+    let per_page = per_page.unwrap_or(20).clamp(1, 100);
+
+    let mut query = User::find().order_by_asc(user::Column::Id);
+    if let Some(q) = q.filter(|q| !q.is_empty()) {
+        let pattern = format!("%{}%", escape_like(&q));
+        query = query.filter(
+            Condition::any()
+                .add(user::Column::Email.like(&pattern))
+                .add(user::Column::Firstname.like(&pattern))
+                .add(user::Column::Lastname.like(&pattern)),
+        );
+    }
+
+    let paginator = query.paginate(db, per_page);
+    let total = paginator.num_items().await?;
+    check_page(page, total.div_ceil(per_page))?;
+    let users = paginator
+        .fetch_page(page - 1)
+        .await?
+        .iter()
+        .map(ResUser::from)
+        .collect::<Vec<_>>();
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResUserList {
+            total,
+            page,
+            per_page,
+            users,
+        }),
+    )))
+}
+
+#[get("/users/<id>")]
+pub async fn show_user(
+    db: &State<DatabaseConnection>,
+    _admin: AdminUser,
+    id: i32,
+) -> Response<Json<ResUser>> {
+    let db = db as &DatabaseConnection;
+
+    let u = find_user(db, id).await?;
+
+    Ok(SuccessResponse((Status::Ok, Json(ResUser::from(&u)))))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqRole {
+    role: String,
+}
+
+#[put("/users/<id>/role", data = "<req_role>")]
+pub async fn update_role(
+    db: &State<DatabaseConnection>,
+    admin: AdminUser,
+    info: ClientInfo,
+    id: i32,
+    req_role: Json<ReqRole>,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+
+    let role: Role = match req_role.role.parse() {
+        Ok(r) => r,
+        Err(e) => return Err(ErrorResponse((Status::UnprocessableEntity, e))),
+    };
+
+    let u = find_user(db, id).await?;
+    let old_role = u.role.to_owned();
+
+    // 至少保留一个可用的管理员，否则只能通过命令行恢复
+    if old_role == Role::Admin.as_str() && role != Role::Admin {
+        let other_admins = User::find()
+            .filter(user::Column::Role.eq(Role::Admin.as_str()))
+            .filter(user::Column::DisabledAt.is_null())
+            .filter(user::Column::Id.ne(id))
+            .count(db)
+            .await?;
+        if other_admins == 0 {
+            return Err(ErrorResponse((
+                Status::UnprocessableEntity,
+                "Cannot remove the role of the last admin.".to_string(),
+            )));
+        }
+    }
+
+    let mut u: user::ActiveModel = u.into();
+    u.role = Set(role.to_string());
+    u.updated_at = Set(Some(DateTimeUtc::from(SystemTime::now())));
+    u.update(db).await?;
+
+    info.record(
+        db,
+        Some(id),
+        EventType::RoleChanged,
+        Some(format!("{} -> {} by admin {}", old_role, role, admin.id)),
+    )
+    .await;
+
+    Ok(SuccessResponse((Status::Ok, "Role updated.".to_string())))
+}
+
+// 停用账号，并使其所有会话失效
+#[post("/users/<id>/disable")]
+pub async fn disable_user(
+    db: &State<DatabaseConnection>,
+    admin: AdminUser,
+    id: i32,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+    let now = DateTimeUtc::from(SystemTime::now());
+
+    if admin.id == id {
+        return Err(ErrorResponse((
+            Status::UnprocessableEntity,
+            "You cannot disable your own account.".to_string(),
+        )));
+    }
+
+    let u = find_user(db, id).await?;
+    if u.disabled_at.is_none() {
+        User::update_many()
+            .col_expr(user::Column::DisabledAt, Expr::value(now))
+            .col_expr(user::Column::UpdatedAt, Expr::value(now))
+            .filter(user::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+
+        revoke_all_sessions(db, id).await?;
+    }
+
+    Ok(SuccessResponse((Status::Ok, "User disabled.".to_string())))
+}
+
+#[post("/users/<id>/enable")]
+pub async fn enable_user(
+    db: &State<DatabaseConnection>,
+    _admin: AdminUser,
+    id: i32,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+
+    find_user(db, id).await?;
+
+    User::update_many()
+        .col_expr(
+            user::Column::DisabledAt,
+            Expr::value(Option::<DateTimeUtc>::None),
+        )
+        .col_expr(
+            user::Column::UpdatedAt,
+            Expr::value(DateTimeUtc::from(SystemTime::now())),
+        )
+        .filter(user::Column::Id.eq(id))
+        .exec(db)
+        .await?;
+
+    Ok(SuccessResponse((Status::Ok, "User enabled.".to_string())))
+}
+
+// 强制重置密码：旧密码立即失效，所有会话退出，并向用户发送重置链接
+#[post("/users/<id>/password-reset")]
+pub async fn force_password_reset(
+    db: &State<DatabaseConnection>,
+    config: &State<AppConfig>,
+    mailer: &State<Box<dyn Mailer>>,
+    _admin: AdminUser,
+    id: i32,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+
+    let u = find_user(db, id).await?;
+
+    User::update_many()
+        .col_expr(
+            user::Column::Password,
+            Expr::value(hash_password(&generate_token(32))?),
+        )
+        .col_expr(
+            user::Column::UpdatedAt,
+            Expr::value(DateTimeUtc::from(SystemTime::now())),
+        )
+        .filter(user::Column::Id.eq(id))
+        .exec(db)
+        .await?;
+
+    revoke_all_sessions(db, id).await?;
+    send_password_reset(db, config, mailer.inner().as_ref(), &u).await?;
+
+    Ok(SuccessResponse((
+        Status::Accepted,
+        "The password has been invalidated and a reset link has been sent.".to_string(),
+    )))
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResAuthEvent {
+    id: i32,
+    user_id: Option<i32>,
+    event_type: String,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    detail: Option<String>,
+    created_at: DateTimeUtc,
+}
+
+impl From<&auth_event::Model> for ResAuthEvent {
+    fn from(value: &auth_event::Model) -> Self {
+        Self {
+            id: value.id,
+            user_id: value.user_id,
+            event_type: value.event_type.to_owned(),
+            ip: value.ip.to_owned(),
+            user_agent: value.user_agent.to_owned(),
+            detail: value.detail.to_owned(),
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResAuthEventList {
+    total: u64,
+    page: u64,
+    per_page: u64,
+    events: Vec<ResAuthEvent>,
+}
+
+// 审计日志查询条件，时间为RFC 3339格式
+#[derive(FromForm)]
+pub struct AuthEventQuery {
+    user_id: Option<i32>,
+    event_type: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    page: Option<u64>,
+    per_page: Option<u64>,
+}
+
+fn parse_time(value: &Option<String>) -> Result<Option<DateTimeUtc>, ErrorResponse> {
+    match value {
+        Some(v) => v.parse::<DateTimeUtc>().map(Some).map_err(|_| {
+            ErrorResponse((
+                Status::UnprocessableEntity,
+                format!("Invalid time: {}, expected RFC 3339.", v),
+            ))
+        }),
+        None => Ok(None),
+    }
+}
+
+#[get("/auth-events?<query..>")]
+pub async fn auth_events(
+    db: &State<DatabaseConnection>,
+    _admin: AdminUser,
+    query: AuthEventQuery,
+) -> Response<Json<ResAuthEventList>> {
+    let db = db as &DatabaseConnection;
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(50).clamp(1, 200);
+
+    let mut select = AuthEvent::find().order_by_desc(auth_event::Column::Id);
+    if let Some(user_id) = query.user_id {
+        select = select.filter(auth_event::Column::UserId.eq(user_id));
+    }
+    if let Some(event_type) = &query.event_type {
+        select = select.filter(auth_event::Column::EventType.eq(event_type));
+    }
+    if let Some(from) = parse_time(&query.from)? {
+        select = select.filter(auth_event::Column::CreatedAt.gte(from));
+    }
+    if let Some(to) = parse_time(&query.to)? {
+        select = select.filter(auth_event::Column::CreatedAt.lt(to));
+    }
+
+    let paginator = select.paginate(db, per_page);
+    let total = paginator.num_items().await?;
+    check_page(page, total.div_ceil(per_page))?;
+    let events = paginator
+        .fetch_page(page - 1)
+        .await?
+        .iter()
+        .map(ResAuthEvent::from)
+        .collect::<Vec<_>>();
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResAuthEventList {
+            total,
+            page,
+            per_page,
+            events,
+        }),
+    )))
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResImpersonation {
+    token: String,
+    expires_in: u64,
+}
+
+// 签发代为操作的短期访问令牌，不附带刷新令牌
+#[post("/users/<id>/impersonate")]
+pub async fn impersonate(
+    db: &State<DatabaseConnection>,
+    config: &State<AppConfig>,
+    keys: &State<KeyStore>,
+    admin: AdminUser,
+    info: ClientInfo,
+    id: i32,
+) -> Response<Json<ResImpersonation>> {
+    let db = db as &DatabaseConnection;
+
+    if admin.api_key_id.is_some() || admin.impersonator_id.is_some() {
+        return Err(ErrorResponse((
+            Status::Forbidden,
+            "Impersonation requires a signed-in admin session.".to_string(),
+        )));
+    }
+
+    let u = find_user(db, id).await?;
+
+    if u.disabled_at.is_some() {
+        return Err(ErrorResponse((
+            Status::UnprocessableEntity,
+            "Cannot impersonate a disabled user.".to_string(),
+        )));
+    }
+    if u.role.parse::<Role>() == Ok(Role::Admin) {
+        return Err(ErrorResponse((
+            Status::Forbidden,
+            "Cannot impersonate another admin.".to_string(),
+        )));
+    }
+
+    let actor = find_user(db, admin.id).await?;
+    let token = sign_access_token(
+        keys,
+        &u,
+        config.impersonation_ttl,
+        Some(Actor {
+            sub: actor.id,
+            sv: actor.session_version,
+        }),
+    );
+
+    // 代为操作必须留下审计记录，写入失败时不签发令牌
+    info.try_record(
+        db,
+        Some(u.id),
+        EventType::ImpersonationStarted,
+        Some(format!("by admin {}", admin.id)),
+    )
+    .await?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResImpersonation {
+            token,
+            expires_in: config.impersonation_ttl,
+        }),
+    )))
+}
+
+// 服务运行时索引目录被占用，无法使用命令行重建，由管理员通过接口触发
+#[post("/search/rebuild")]
+pub async fn rebuild_search_index(
+    db: &State<DatabaseConnection>,
+    search: &State<SearchIndex>,
+    _admin: AdminUser,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+
+    let count = search
+        .rebuild(db)
+        .await
+        .map_err(|e| ErrorResponse((Status::InternalServerError, e)))?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        format!("Search index rebuilt with {} documents.", count),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use rocket::http::{ContentType, Header};
+
+    #[rocket::async_test]
+    async fn the_last_admin_cannot_be_demoted() {
+        let app = testing::app(|_| {}).await;
+        let admin = testing::create_user(&app.db, "admin@example.com", Role::Admin).await;
+        let token = testing::sign_in(&app.client, "admin@example.com").await;
+        let demote = || {
+            app.client
+                .put(format!("/admin/users/{}/role", admin.id))
+                .remote("127.0.0.1:8000".parse().unwrap())
+                .header(Header::new("Authorization", format!("Bearer {}", token)))
+                .header(ContentType::JSON)
+                .body(r#"{"role": "user"}"#)
+                .dispatch()
+        };
+
+        assert_eq!(demote().await.status(), Status::UnprocessableEntity);
+
+        testing::create_user(&app.db, "other@example.com", Role::Admin).await;
+        assert_eq!(demote().await.status(), Status::Ok);
+
+        let event = AuthEvent::find()
+            .filter(auth_event::Column::EventType.eq(EventType::RoleChanged.as_str()))
+            .one(&app.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.user_id, Some(admin.id));
+        assert_eq!(
+            event.detail.as_deref(),
+            Some(format!("admin -> user by admin {}", admin.id).as_str())
+        );
+    }
+
+    #[rocket::async_test]
+    async fn impersonation_ends_when_the_admin_signs_out_everywhere() {
+        let app = testing::app(|_| {}).await;
+        let admin = testing::create_user(&app.db, "admin@example.com", Role::Admin).await;
+        let u = testing::create_user(&app.db, "reader@example.com", Role::User).await;
+        let token = testing::sign_in(&app.client, "admin@example.com").await;
+
+        let res = app
+            .client
+            .post(format!("/admin/users/{}/impersonate", u.id))
+            .remote("127.0.0.1:8000".parse().unwrap())
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch()
+            .await;
+        assert_eq!(res.status(), Status::Ok);
+        let body: rocket::serde::json::Value = res.into_json().await.unwrap();
+        let impersonation = body["token"].as_str().unwrap().to_string();
+        let me = || {
+            app.client
+                .get("/auth/me")
+                .remote("127.0.0.1:8000".parse().unwrap())
+                .header(Header::new(
+                    "Authorization",
+                    format!("Bearer {}", impersonation),
+                ))
+                .dispatch()
+        };
+        assert_eq!(me().await.status(), Status::Ok);
+
+        revoke_all_sessions(&app.db, admin.id).await.unwrap();
+        assert_eq!(me().await.status(), Status::Unauthorized);
+    }
+}
@@ -0,0 +1,185 @@
+use std::time::{Duration, SystemTime};
+
+use rocket::{
+    http::Status,
+    serde::{json::Json, Deserialize, Serialize},
+    State,
+};
+use sea_orm::{prelude::DateTimeUtc, *};
+
+use super::{ErrorResponse, Response, SuccessResponse};
+use crate::auth::{generate_token, hash_token, SessionUser, API_KEY_PREFIX, SCOPES};
+use crate::entities::{api_key, prelude::*};
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResApiKey {
+    id: i32,
+    name: String,
+    prefix: String,
+    scopes: Option<Vec<String>>,
+    last_used_at: Option<DateTimeUtc>,
+    expires_at: Option<DateTimeUtc>,
+    revoked_at: Option<DateTimeUtc>,
+    created_at: Option<DateTimeUtc>,
+}
+
+impl From<&api_key::Model> for ResApiKey {
+    fn from(value: &api_key::Model) -> Self {
+        Self {
+            id: value.id,
+            name: value.name.to_owned(),
+            prefix: value.prefix.to_owned(),
+            scopes: value
+                .scopes
+                .as_ref()
+                .map(|s| s.split(',').map(|s| s.to_string()).collect()),
+            last_used_at: value.last_used_at,
+            expires_at: value.expires_at,
+            revoked_at: value.revoked_at,
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResApiKeyList {
+    total: usize,
+    api_keys: Vec<ResApiKey>,
+}
+
+// 创建时返回完整密钥，之后只能看到前缀
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResApiKeyCreated {
+    key: String,
+    api_key: ResApiKey,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqApiKey {
+    name: String,
+    scopes: Option<Vec<String>>,
+    expires_in_days: Option<u64>,
+}
+
+#[get("/")]
+pub async fn index(
+    db: &State<DatabaseConnection>,
+    user: SessionUser,
+) -> Response<Json<ResApiKeyList>> {
+    let db = db as &DatabaseConnection;
+
+    let api_keys = ApiKey::find()
+        .filter(api_key::Column::UserId.eq(user.id))
+        .order_by_desc(api_key::Column::CreatedAt)
+        .all(db)
+        .await?
+        .iter()
+        .map(ResApiKey::from)
+        .collect::<Vec<_>>();
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResApiKeyList {
+            total: api_keys.len(),
+            api_keys,
+        }),
+    )))
+}
+
+#[post("/", data = "<req_api_key>")]
+pub async fn create(
+    db: &State<DatabaseConnection>,
+    user: SessionUser,
+    req_api_key: Json<ReqApiKey>,
+) -> Response<Json<ResApiKeyCreated>> {
+    let db = db as &DatabaseConnection;
+
+    if let Some(scopes) = &req_api_key.scopes {
+        if let Some(s) = scopes.iter().find(|s| !SCOPES.contains(&s.as_str())) {
+            return Err(ErrorResponse((
+                Status::UnprocessableEntity,
+                format!("Unknown scope: {}", s),
+            )));
+        }
+    }
+
+    let key = format!("{}{}", API_KEY_PREFIX, generate_token(24));
+
+    let api_key = api_key::ActiveModel {
+        user_id: Set(user.id),
+        name: Set(req_api_key.name.to_owned()),
+        prefix: Set(key[..API_KEY_PREFIX.len() + 8].to_string()),
+        key_hash: Set(hash_token(&key)),
+        scopes: Set(req_api_key.scopes.as_ref().map(|s| s.join(","))),
+        expires_at: Set(expires_after_days(req_api_key.expires_in_days)?),
+        ..Default::default()
+    };
+
+    let api_key = api_key.insert(db).await?;
+
+    Ok(SuccessResponse((
+        Status::Created,
+        Json(ResApiKeyCreated {
+            key,
+            api_key: ResApiKey::from(&api_key),
+        }),
+    )))
+}
+
+// 有效期上限为10年
+const MAX_EXPIRES_IN_DAYS: u64 = 10 * 365;
+
+// 根据有效天数计算过期时间，超出上限时返回422
+pub(crate) fn expires_after_days(days: Option<u64>) -> Result<Option<DateTimeUtc>, ErrorResponse> {
+    let days = match days {
+        Some(days) => days,
+        None => return Ok(None),
+    };
+
+    days.checked_mul(24 * 60 * 60)
+        .filter(|_| (1..=MAX_EXPIRES_IN_DAYS).contains(&days))
+        .and_then(|secs| SystemTime::now().checked_add(Duration::from_secs(secs)))
+        .map(|t| Some(DateTimeUtc::from(t)))
+        .ok_or(ErrorResponse((
+            Status::UnprocessableEntity,
+            format!(
+                "expires_in_days must be between 1 and {}.",
+                MAX_EXPIRES_IN_DAYS
+            ),
+        )))
+}
+
+#[delete("/<id>")]
+pub async fn revoke(
+    db: &State<DatabaseConnection>,
+    user: SessionUser,
+    id: i32,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+
+    let mut api_key: api_key::ActiveModel = match ApiKey::find_by_id(id)
+        .filter(api_key::Column::UserId.eq(user.id))
+        .one(db)
+        .await?
+    {
+        Some(k) => k.into(),
+        None => {
+            return Err(ErrorResponse((
+                Status::NotFound,
+                "No API key with the specified ID.".to_string(),
+            )));
+        }
+    };
+
+    api_key.revoked_at = Set(Some(DateTimeUtc::from(SystemTime::now())));
+    api_key.update(db).await?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        "API key revoked.".to_string(),
+    )))
+}
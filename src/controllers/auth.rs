@@ -1,19 +1,31 @@
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
-use bcrypt::{hash, verify, DEFAULT_COST};
-use jsonwebtoken::{encode, EncodingKey, Header};
 use rocket::{
     http::Status,
-    serde::{self, json::Json, Deserialize, Serialize},
+    serde::{json::Json, Deserialize, Serialize},
     State,
 };
-use sea_orm::*;
+use sea_orm::{prelude::DateTimeUtc, sea_query::Expr, *};
 
-use super::{Response, SuccessResponse};
+use super::{LimitedError, LimitedResponse, Response, SuccessResponse, TooManyRequests};
 
-use crate::entities::{prelude::*, user};
-use crate::AppConfig;
-use crate::{auth::AuthenticatedUser, controllers::ErrorResponse};
+use crate::entities::{
+    email_verification_token, invitation, password_reset_token, prelude::*, refresh_token,
+    revoked_token, user,
+};
+use crate::mailer::{Mail, Mailer};
+use crate::{AppConfig, RegistrationMode, UnverifiedPolicy};
+use crate::{
+    auth::{
+        audit::{ClientInfo, EventType},
+        generate_token, hash_token,
+        keys::KeyStore,
+        password::{hash_password, needs_rehash, verify_password, PasswordPolicy},
+        throttle::{ClientAddr, LoginThrottle, ResetThrottle},
+        totp, Actor, AuthenticatedUser, Claims, SessionUser,
+    },
+    controllers::ErrorResponse,
+};
 
 #[derive(Deserialize)]
 #[serde(crate = "rocket::serde")]
@@ -22,66 +34,374 @@ pub struct ReqSignIn {
     password: String,
 }
 
-#[derive(Serialize, Deserialize, Responder)]
+#[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct ResSignIn {
     token: String,
+    refresh_token: String,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+// 启用两步验证的账号登录时，先返回临时令牌
+#[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
-struct Claims {
-    sub: i32,
-    role: String, // 角色
-    exp: u64,     // 过期时间
+pub struct ResChallenge {
+    challenge_token: String,
+    two_factor_required: bool,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde", untagged)]
+pub enum ResSignInStep {
+    Tokens(ResSignIn),
+    Challenge(ResChallenge),
+}
+
+// 启用两步验证的账号还需要通过/auth/2fa/verify才能取得令牌
+pub(crate) fn two_factor_challenge(
+    config: &AppConfig,
+    keys: &KeyStore,
+    u: &user::Model,
+) -> ResSignInStep {
+    ResSignInStep::Challenge(ResChallenge {
+        challenge_token: totp::encode_challenge(config, keys, u.id),
+        two_factor_required: true,
+    })
+}
+
+// 签发访问令牌（JWT）
+fn encode_access_token(config: &AppConfig, keys: &KeyStore, u: &user::Model) -> String {
+    sign_access_token(keys, u, config.access_token_ttl, None)
+}
+
+// act不为空时表示管理员代为操作的令牌
+pub(crate) fn sign_access_token(
+    keys: &KeyStore,
+    u: &user::Model,
+    ttl: u64,
+    act: Option<Actor>,
+) -> String {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let claims = Claims {
+        sub: u.id,
+        role: u.role.to_owned(),
+        exp: now + ttl,
+        iat: now,
+        jti: generate_token(16),
+        sv: u.session_version,
+        act,
+    };
+
+    keys.encode(&claims)
+}
+
+// 签发访问令牌和刷新令牌，family为空时开启新的令牌家族
+pub(crate) async fn issue_tokens(
+    db: &DatabaseConnection,
+    config: &AppConfig,
+    keys: &KeyStore,
+    u: &user::Model,
+    family: Option<String>,
+) -> Result<ResSignIn, ErrorResponse> {
+    let refresh_token = generate_token(32);
+
+    RefreshToken::insert(refresh_token::ActiveModel {
+        user_id: Set(u.id),
+        token_hash: Set(hash_token(&refresh_token)),
+        family: Set(family.unwrap_or_else(|| generate_token(16))),
+        expires_at: Set(DateTimeUtc::from(
+            SystemTime::now() + Duration::from_secs(config.refresh_token_ttl),
+        )),
+        ..Default::default()
+    })
+    .exec(db)
+    .await?;
+
+    Ok(ResSignIn {
+        token: encode_access_token(config, keys, u),
+        refresh_token,
+    })
+}
+
+// 连续失败达到上限后锁定账号，并重新计数。计数在数据库中原子递增，
+// 并发的失败请求中只有一个能把计数清零并锁定账号
+pub(crate) async fn count_failure(
+    db: &DatabaseConnection,
+    config: &AppConfig,
+    info: &ClientInfo,
+    user_id: i32,
+    counter: user::Column,
+) -> Result<(), DbErr> {
+    User::update_many()
+        .col_expr(counter, Expr::col(counter).add(1))
+        .filter(user::Column::Id.eq(user_id))
+        .exec(db)
+        .await?;
+
+    let locked = User::update_many()
+        .col_expr(counter, Expr::value(0))
+        .col_expr(
+            user::Column::LockedUntil,
+            Expr::value(DateTimeUtc::from(
+                SystemTime::now() + Duration::from_secs(config.login_lockout_secs),
+            )),
+        )
+        .filter(user::Column::Id.eq(user_id))
+        .filter(counter.gte(config.login_max_failures))
+        .exec(db)
+        .await?;
+    if locked.rows_affected > 0 {
+        info.record(db, Some(user_id), EventType::AccountLocked, None)
+            .await;
+    }
+
+    Ok(())
+}
+
+// 账号处于锁定期时返回429
+pub(crate) async fn ensure_not_locked(
+    db: &DatabaseConnection,
+    info: &ClientInfo,
+    u: &user::Model,
+) -> Result<(), LimitedError> {
+    let now = DateTimeUtc::from(SystemTime::now());
+
+    if let Some(locked_until) = u.locked_until {
+        if locked_until > now {
+            info.record(
+                db,
+                Some(u.id),
+                EventType::SignInFailed,
+                Some("account locked".to_string()),
+            )
+            .await;
+            let secs = (locked_until - now).num_seconds().max(1) as u64;
+            return Err(TooManyRequests::retry_after(secs).into());
+        }
+    }
+
+    Ok(())
+}
+
+// 身份验证通过后检查账号能否登录：未被停用，且满足未验证邮箱的限制。
+// 密码登录、两步验证和OIDC登录共用
+pub(crate) async fn ensure_active(
+    db: &DatabaseConnection,
+    config: &AppConfig,
+    info: &ClientInfo,
+    u: &user::Model,
+) -> Result<(), ErrorResponse> {
+    if u.disabled_at.is_some() {
+        info.record(
+            db,
+            Some(u.id),
+            EventType::SignInFailed,
+            Some("account disabled".to_string()),
+        )
+        .await;
+        return Err(ErrorResponse((
+            Status::Forbidden,
+            "This account has been disabled.".to_string(),
+        )));
+    }
+
+    if u.verified_at.is_none() && config.unverified_policy == UnverifiedPolicy::BlockSignIn {
+        return Err(ErrorResponse((
+            Status::Forbidden,
+            "Please verify your email address before signing in.".to_string(),
+        )));
+    }
+
+    Ok(())
 }
 
 #[post("/sign-in", data = "<req_sign_in>")]
 pub async fn sigin_in(
     db: &State<DatabaseConnection>,
     config: &State<AppConfig>,
+    keys: &State<KeyStore>,
+    throttle: &State<LoginThrottle>,
+    client: ClientAddr,
+    info: ClientInfo,
     req_sign_in: Json<ReqSignIn>,
-) -> Response<Json<ResSignIn>> {
+) -> LimitedResponse<Json<ResSignInStep>> {
     let db = db as &DatabaseConnection;
     let config = config as &AppConfig;
+
+    throttle
+        .check(&client.ip)
+        .map_err(TooManyRequests::retry_after)?;
+
     let u: user::Model = match User::find()
         .filter(user::Column::Email.eq(&req_sign_in.email))
         .one(db)
         .await?
     {
         Some(u) => u,
+        None => {
+            throttle.record_failure(client.ip);
+            info.record(
+                db,
+                None,
+                EventType::SignInFailed,
+                Some(req_sign_in.email.to_owned()),
+            )
+            .await;
+            return Err(
+                ErrorResponse((Status::Unauthorized, "Invalid credentials".to_string())).into(),
+            );
+        }
+    };
+
+    // 锁定期内不校验密码，避免通过不同的响应继续猜测密码
+    ensure_not_locked(db, &info, &u).await?;
+
+    if !verify_password(&req_sign_in.password, &u.password) {
+        throttle.record_failure(client.ip);
+        info.record(db, Some(u.id), EventType::SignInFailed, None)
+            .await;
+
+        count_failure(db, config, &info, u.id, user::Column::FailedLogins).await?;
+
+        return Err(
+            ErrorResponse((Status::Unauthorized, "Invalid credentials".to_string())).into(),
+        );
+    }
+
+    // 旧的bcrypt哈希在登录成功后升级为Argon2id
+    if needs_rehash(&u.password) {
+        User::update_many()
+            .col_expr(
+                user::Column::Password,
+                Expr::value(hash_password(&req_sign_in.password)?),
+            )
+            .filter(user::Column::Id.eq(u.id))
+            .exec(db)
+            .await?;
+    }
+
+    if u.failed_logins > 0 || u.locked_until.is_some() {
+        User::update_many()
+            .col_expr(user::Column::FailedLogins, Expr::value(0))
+            .col_expr(
+                user::Column::LockedUntil,
+                Expr::value(Option::<DateTimeUtc>::None),
+            )
+            .filter(user::Column::Id.eq(u.id))
+            .exec(db)
+            .await?;
+    }
+
+    ensure_active(db, config, &info, &u).await?;
+
+    if u.totp_enabled_at.is_some() {
+        return Ok(SuccessResponse((
+            Status::Ok,
+            Json(two_factor_challenge(config, keys, &u)),
+        )));
+    }
+
+    let res = issue_tokens(db, config, keys, &u, None).await?;
+    info.record(db, Some(u.id), EventType::SignIn, None).await;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResSignInStep::Tokens(res)),
+    )))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqRefresh {
+    refresh_token: String,
+}
+
+// 已轮换的令牌被再次使用，说明令牌可能泄露，吊销整个家族
+async fn reuse_detected(
+    db: &DatabaseConnection,
+    info: &ClientInfo,
+    token: &refresh_token::Model,
+    now: DateTimeUtc,
+) -> Response<Json<ResSignIn>> {
+    RefreshToken::update_many()
+        .col_expr(refresh_token::Column::RevokedAt, Expr::value(now))
+        .filter(refresh_token::Column::Family.eq(&token.family))
+        .filter(refresh_token::Column::RevokedAt.is_null())
+        .exec(db)
+        .await?;
+    info.record(db, Some(token.user_id), EventType::RefreshReuse, None)
+        .await;
+
+    Err(ErrorResponse((
+        Status::Unauthorized,
+        "Refresh token reuse detected".to_string(),
+    )))
+}
+
+#[post("/refresh", data = "<req_refresh>")]
+pub async fn refresh(
+    db: &State<DatabaseConnection>,
+    config: &State<AppConfig>,
+    keys: &State<KeyStore>,
+    info: ClientInfo,
+    req_refresh: Json<ReqRefresh>,
+) -> Response<Json<ResSignIn>> {
+    let db = db as &DatabaseConnection;
+    let config = config as &AppConfig;
+    let now = DateTimeUtc::from(SystemTime::now());
+
+    let token = match RefreshToken::find()
+        .filter(refresh_token::Column::TokenHash.eq(hash_token(&req_refresh.refresh_token)))
+        .one(db)
+        .await?
+    {
+        Some(t) => t,
         None => {
             return Err(ErrorResponse((
                 Status::Unauthorized,
-                "Invalid credentials".to_string(),
+                "Invalid refresh token".to_string(),
             )));
         }
     };
-    if !verify(&req_sign_in.password, &u.password).unwrap() {
+
+    if token.revoked_at.is_some() {
+        return reuse_detected(db, &info, &token, now).await;
+    }
+
+    if token.expires_at <= now {
         return Err(ErrorResponse((
             Status::Unauthorized,
-            "Invalid credentials".to_string(),
+            "Refresh token expired".to_string(),
         )));
     }
 
-    let claims = Claims {
-        sub: u.id,
-        role: "user".to_string(),
-        exp: SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
-            + 4 * 60 * 60,
+    let u = match User::find_by_id(token.user_id).one(db).await? {
+        Some(u) if u.disabled_at.is_none() => u,
+        _ => {
+            return Err(ErrorResponse((
+                Status::Unauthorized,
+                "Invalid refresh token".to_string(),
+            )));
+        }
     };
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(config.jwt_sercert.as_bytes()),
-    )
-    .unwrap();
+    // 轮换：旧令牌作废，同一家族签发新令牌。
+    // 条件更新保证并发请求中只有一个能作废旧令牌，其余按重复使用处理
+    let revoked = RefreshToken::update_many()
+        .col_expr(refresh_token::Column::RevokedAt, Expr::value(now))
+        .filter(refresh_token::Column::Id.eq(token.id))
+        .filter(refresh_token::Column::RevokedAt.is_null())
+        .exec(db)
+        .await?;
+    if revoked.rows_affected == 0 {
+        return reuse_detected(db, &info, &token, now).await;
+    }
+
+    let res = issue_tokens(db, config, keys, &u, Some(token.family)).await?;
 
-    Ok(SuccessResponse((Status::Ok, Json(ResSignIn { token }))))
+    Ok(SuccessResponse((Status::Ok, Json(res))))
 }
 
 #[derive(Deserialize)]
@@ -91,14 +411,67 @@ pub struct ReqSignUp {
     password: String,
     firstname: Option<String>,
     lastname: Option<String>,
+    invitation_code: Option<String>,
+}
+
+// 查找可用的邀请码（未使用且未过期）
+async fn find_invitation(
+    db: &DatabaseConnection,
+    code: &str,
+) -> Result<invitation::Model, ErrorResponse> {
+    let now = DateTimeUtc::from(SystemTime::now());
+
+    match Invitation::find()
+        .filter(invitation::Column::CodeHash.eq(hash_token(code.trim())))
+        .filter(invitation::Column::UsedAt.is_null())
+        .filter(
+            Condition::any()
+                .add(invitation::Column::ExpiresAt.is_null())
+                .add(invitation::Column::ExpiresAt.gt(now)),
+        )
+        .one(db)
+        .await?
+    {
+        Some(i) => Ok(i),
+        None => Err(ErrorResponse((
+            Status::Forbidden,
+            "Invalid or expired invitation code.".to_string(),
+        ))),
+    }
 }
 
 #[post("/sign-up", data = "<req_sign_up>")]
 pub async fn sigin_up(
     db: &State<DatabaseConnection>,
+    config: &State<AppConfig>,
+    mailer: &State<Box<dyn Mailer>>,
+    policy: &State<PasswordPolicy>,
+    info: ClientInfo,
     req_sign_up: Json<ReqSignUp>,
 ) -> Response<String> {
     let db = db as &DatabaseConnection;
+    let config = config as &AppConfig;
+
+    policy.check(&req_sign_up.password)?;
+
+    let invitation = match config.registration_mode {
+        RegistrationMode::Closed => {
+            return Err(ErrorResponse((
+                Status::Forbidden,
+                "Registration is closed.".to_string(),
+            )));
+        }
+        mode => match &req_sign_up.invitation_code {
+            Some(code) => Some(find_invitation(db, code).await?),
+            None if mode == RegistrationMode::InviteOnly => {
+                return Err(ErrorResponse((
+                    Status::Forbidden,
+                    "An invitation code is required to sign up.".to_string(),
+                )));
+            }
+            None => None,
+        },
+    };
 
     if User::find()
         .filter(user::Column::Email.eq(&req_sign_up.email))
@@ -112,15 +485,71 @@ pub async fn sigin_up(
         )));
     }
 
-    User::insert(user::ActiveModel {
+    let txn = db.begin().await?;
+
+    let mut u = user::ActiveModel {
         email: Set(req_sign_up.email.to_owned()),
-        password: Set(hash(req_sign_up.password.to_owned(), DEFAULT_COST).unwrap()),
+        password: Set(hash_password(&req_sign_up.password)?),
         firstname: Set(req_sign_up.firstname.to_owned()),
         lastname: Set(req_sign_up.lastname.to_owned()),
         ..Default::default()
-    })
-    .exec(db)
-    .await?;
+    };
+    if let Some(i) = &invitation {
+        u.role = Set(i.role.to_owned());
+    }
+
+    // 并发注册同一邮箱时由唯一索引兜底
+    let res = match User::insert(u).exec(&txn).await {
+        Ok(res) => res,
+        Err(err) => {
+            return Err(match err.sql_err() {
+                Some(SqlErr::UniqueConstraintViolation(_)) => ErrorResponse((
+                    Status::UnprocessableEntity,
+                    "An account exists with that email address.".to_string(),
+                )),
+                _ => err.into(),
+            });
+        }
+    };
+
+    // 只有仍未使用的邀请码才能被占用，防止并发注册重复使用
+    if let Some(i) = &invitation {
+        let claimed = Invitation::update_many()
+            .col_expr(
+                invitation::Column::UsedAt,
+                Expr::value(DateTimeUtc::from(SystemTime::now())),
+            )
+            .col_expr(invitation::Column::UsedBy, Expr::value(res.last_insert_id))
+            .filter(invitation::Column::Id.eq(i.id))
+            .filter(invitation::Column::UsedAt.is_null())
+            .exec(&txn)
+            .await?;
+
+        if claimed.rows_affected == 0 {
+            return Err(ErrorResponse((
+                Status::Forbidden,
+                "Invalid or expired invitation code.".to_string(),
+            )));
+        }
+    }
+
+    txn.commit().await?;
+    info.record(db, Some(res.last_insert_id), EventType::SignUp, None)
+        .await;
+
+    // 账号已经创建，邮件发送失败时用户可以稍后重新请求验证邮件
+    if let Err(ErrorResponse((_, err))) = send_verification(
+        db,
+        config,
+        mailer.inner().as_ref(),
+        res.last_insert_id,
+        &req_sign_up.email,
+    )
+    .await
+    {
+        error!("发送验证邮件失败：{}", err);
+    }
+
     Ok(SuccessResponse((
         Status::Created,
         "Account created!".to_string(),
@@ -134,6 +563,24 @@ pub struct ResMe {
     email: String,
     firstname: Option<String>,
     lastname: Option<String>,
+    role: String,
+    verified: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    impersonated_by: Option<i32>, // 代为操作的管理员ID
+}
+
+impl From<&user::Model> for ResMe {
+    fn from(value: &user::Model) -> Self {
+        Self {
+            id: value.id,
+            email: value.email.to_owned(),
+            firstname: value.firstname.to_owned(),
+            lastname: value.lastname.to_owned(),
+            role: value.role.to_owned(),
+            verified: value.verified_at.is_some(),
+            impersonated_by: None,
+        }
+    }
 }
 
 #[get("/me")]
@@ -141,13 +588,486 @@ pub async fn me(db: &State<DatabaseConnection>, user: AuthenticatedUser) -> Resp
     let db = db as &DatabaseConnection;
     let u = User::find_by_id(user.id).one(db).await?.unwrap();
 
+    let mut res = ResMe::from(&u);
+    res.impersonated_by = user.impersonator_id;
+
+    Ok(SuccessResponse((Status::Ok, Json(res))))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqSignOut {
+    refresh_token: Option<String>,
+}
+
+// 退出当前会话：访问令牌加入黑名单，同时吊销提交的刷新令牌
+#[post("/sign-out", data = "<req_sign_out>")]
+pub async fn sign_out(
+    db: &State<DatabaseConnection>,
+    user: SessionUser,
+    info: ClientInfo,
+    req_sign_out: Option<Json<ReqSignOut>>,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+    let now = DateTimeUtc::from(SystemTime::now());
+
+    // 顺便清理已过期的黑名单记录
+    RevokedToken::delete_many()
+        .filter(revoked_token::Column::ExpiresAt.lt(now))
+        .exec(db)
+        .await?;
+
+    RevokedToken::insert(revoked_token::ActiveModel {
+        jti: Set(user.jti.to_owned()),
+        user_id: Set(user.id),
+        expires_at: Set(DateTimeUtc::from(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(user.exp),
+        )),
+        ..Default::default()
+    })
+    .exec(db)
+    .await?;
+
+    if let Some(refresh_token) = req_sign_out.and_then(|r| r.into_inner().refresh_token) {
+        RefreshToken::update_many()
+            .col_expr(refresh_token::Column::RevokedAt, Expr::value(now))
+            .filter(refresh_token::Column::TokenHash.eq(hash_token(&refresh_token)))
+            .filter(refresh_token::Column::UserId.eq(user.id))
+            .filter(refresh_token::Column::RevokedAt.is_null())
+            .exec(db)
+            .await?;
+    }
+
+    info.record(db, Some(user.id), EventType::SignOut, None)
+        .await;
+
+    Ok(SuccessResponse((Status::Ok, "Signed out.".to_string())))
+}
+
+// 退出所有会话：此前签发的访问令牌和刷新令牌全部失效
+#[post("/sign-out-all")]
+pub async fn sign_out_all(
+    db: &State<DatabaseConnection>,
+    user: SessionUser,
+    info: ClientInfo,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+
+    revoke_all_sessions(db, user.id).await?;
+    info.record(db, Some(user.id), EventType::SignOutAll, None)
+        .await;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        "Signed out of all sessions.".to_string(),
+    )))
+}
+
+pub(crate) async fn revoke_all_sessions(
+    db: &DatabaseConnection,
+    user_id: i32,
+) -> Result<(), DbErr> {
+    let now = DateTimeUtc::from(SystemTime::now());
+
+    User::update_many()
+        .col_expr(
+            user::Column::SessionVersion,
+            Expr::col(user::Column::SessionVersion).add(1),
+        )
+        .filter(user::Column::Id.eq(user_id))
+        .exec(db)
+        .await?;
+
+    RefreshToken::update_many()
+        .col_expr(refresh_token::Column::RevokedAt, Expr::value(now))
+        .filter(refresh_token::Column::UserId.eq(user_id))
+        .filter(refresh_token::Column::RevokedAt.is_null())
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqForgotPassword {
+    email: String,
+}
+
+#[post("/forgot-password", data = "<req_forgot>")]
+pub async fn forgot_password(
+    db: &State<DatabaseConnection>,
+    config: &State<AppConfig>,
+    mailer: &State<Box<dyn Mailer>>,
+    throttle: &State<ResetThrottle>,
+    req_forgot: Json<ReqForgotPassword>,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+    let config = config as &AppConfig;
+
+    // 无论邮箱是否存在、是否被限流、邮件是否发送成功都返回相同结果，避免泄露注册信息
+    let res = SuccessResponse((
+        Status::Ok,
+        "If an account exists with that email address, a reset link has been sent.".to_string(),
+    ));
+
+    let email = req_forgot.email.trim().to_lowercase();
+    if throttle.check(&email).is_err() {
+        return Ok(res);
+    }
+    throttle.record_failure(email);
+
+    let u = match User::find()
+        .filter(user::Column::Email.eq(&req_forgot.email))
+        .one(db)
+        .await?
+    {
+        Some(u) => u,
+        None => return Ok(res),
+    };
+
+    if let Err(ErrorResponse((_, err))) =
+        send_password_reset(db, config, mailer.inner().as_ref(), &u).await
+    {
+        error!("发送重置密码邮件失败：{}", err);
+    }
+
+    Ok(res)
+}
+
+// 生成重置密码令牌并发送重置邮件
+pub(crate) async fn send_password_reset(
+    db: &DatabaseConnection,
+    config: &AppConfig,
+    mailer: &dyn Mailer,
+    u: &user::Model,
+) -> Result<(), ErrorResponse> {
+    // 之前未使用的令牌作废
+    PasswordResetToken::delete_many()
+        .filter(password_reset_token::Column::UserId.eq(u.id))
+        .filter(password_reset_token::Column::UsedAt.is_null())
+        .exec(db)
+        .await?;
+
+    let token = generate_token(32);
+
+    PasswordResetToken::insert(password_reset_token::ActiveModel {
+        user_id: Set(u.id),
+        token_hash: Set(hash_token(&token)),
+        expires_at: Set(DateTimeUtc::from(
+            SystemTime::now() + Duration::from_secs(config.reset_token_ttl),
+        )),
+        ..Default::default()
+    })
+    .exec(db)
+    .await?;
+
+    mailer
+        .send(Mail {
+            to: u.email.to_owned(),
+            subject: "Reset your BookStore password".to_string(),
+            body: format!(
+                "Use the link below to reset your password. It expires in {} minutes.\n\n{}/reset-password?token={}\n",
+                config.reset_token_ttl / 60,
+                config.app_url,
+                token
+            ),
+        })
+        .await
+        .map_err(|e| ErrorResponse((Status::InternalServerError, e)))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqResetPassword {
+    token: String,
+    password: String,
+}
+
+#[post("/reset-password", data = "<req_reset>")]
+pub async fn reset_password(
+    db: &State<DatabaseConnection>,
+    policy: &State<PasswordPolicy>,
+    info: ClientInfo,
+    req_reset: Json<ReqResetPassword>,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+    let now = DateTimeUtc::from(SystemTime::now());
+
+    policy.check(&req_reset.password)?;
+
+    let token = match PasswordResetToken::find()
+        .filter(password_reset_token::Column::TokenHash.eq(hash_token(&req_reset.token)))
+        .filter(password_reset_token::Column::UsedAt.is_null())
+        .filter(password_reset_token::Column::ExpiresAt.gt(now))
+        .one(db)
+        .await?
+    {
+        Some(t) => t,
+        None => {
+            return Err(ErrorResponse((
+                Status::BadRequest,
+                "Invalid or expired reset token.".to_string(),
+            )));
+        }
+    };
+
+    let password = hash_password(&req_reset.password)?;
+
+    // 条件更新保证令牌只能使用一次，并发请求中只有一个能成功
+    let claimed = PasswordResetToken::update_many()
+        .col_expr(password_reset_token::Column::UsedAt, Expr::value(now))
+        .filter(password_reset_token::Column::Id.eq(token.id))
+        .filter(password_reset_token::Column::UsedAt.is_null())
+        .exec(db)
+        .await?;
+    if claimed.rows_affected == 0 {
+        return Err(ErrorResponse((
+            Status::BadRequest,
+            "Invalid or expired reset token.".to_string(),
+        )));
+    }
+
+    let user_id = token.user_id;
+    User::update_many()
+        .col_expr(user::Column::Password, Expr::value(password))
+        .col_expr(user::Column::UpdatedAt, Expr::value(now))
+        .filter(user::Column::Id.eq(user_id))
+        .exec(db)
+        .await?;
+
+    // 重置密码后所有已登录会话失效
+    revoke_all_sessions(db, user_id).await?;
+    info.record(db, Some(user_id), EventType::PasswordReset, None)
+        .await;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        "Password has been reset.".to_string(),
+    )))
+}
+
+// 生成邮箱验证令牌并发送验证邮件
+async fn send_verification(
+    db: &DatabaseConnection,
+    config: &AppConfig,
+    mailer: &dyn Mailer,
+    user_id: i32,
+    email: &str,
+) -> Result<(), ErrorResponse> {
+    EmailVerificationToken::delete_many()
+        .filter(email_verification_token::Column::UserId.eq(user_id))
+        .exec(db)
+        .await?;
+
+    let token = generate_token(32);
+
+    EmailVerificationToken::insert(email_verification_token::ActiveModel {
+        user_id: Set(user_id),
+        token_hash: Set(hash_token(&token)),
+        expires_at: Set(DateTimeUtc::from(
+            SystemTime::now() + Duration::from_secs(config.verify_token_ttl),
+        )),
+        ..Default::default()
+    })
+    .exec(db)
+    .await?;
+
+    mailer
+        .send(Mail {
+            to: email.to_owned(),
+            subject: "Verify your BookStore email address".to_string(),
+            body: format!(
+                "Use the link below to verify your email address.\n\n{}/verify?token={}\n",
+                config.app_url, token
+            ),
+        })
+        .await
+        .map_err(|e| ErrorResponse((Status::InternalServerError, e)))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqVerify {
+    token: String,
+}
+
+#[post("/verify", data = "<req_verify>")]
+pub async fn verify_email(
+    db: &State<DatabaseConnection>,
+    req_verify: Json<ReqVerify>,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+    let now = DateTimeUtc::from(SystemTime::now());
+
+    let token = match EmailVerificationToken::find()
+        .filter(email_verification_token::Column::TokenHash.eq(hash_token(&req_verify.token)))
+        .filter(email_verification_token::Column::ExpiresAt.gt(now))
+        .one(db)
+        .await?
+    {
+        Some(t) => t,
+        None => {
+            return Err(ErrorResponse((
+                Status::BadRequest,
+                "Invalid or expired verification token.".to_string(),
+            )));
+        }
+    };
+
+    User::update_many()
+        .col_expr(user::Column::VerifiedAt, Expr::value(now))
+        .filter(user::Column::Id.eq(token.user_id))
+        .exec(db)
+        .await?;
+
+    token.delete(db).await?;
+
+    Ok(SuccessResponse((Status::Ok, "Email verified.".to_string())))
+}
+
+#[post("/verify/resend")]
+pub async fn resend_verification(
+    db: &State<DatabaseConnection>,
+    config: &State<AppConfig>,
+    mailer: &State<Box<dyn Mailer>>,
+    user: SessionUser,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+
+    if user.verified {
+        return Err(ErrorResponse((
+            Status::UnprocessableEntity,
+            "Email address already verified.".to_string(),
+        )));
+    }
+
+    let u = User::find_by_id(user.id).one(db).await?.unwrap();
+
+    send_verification(db, config, mailer.inner().as_ref(), u.id, &u.email).await?;
+
     Ok(SuccessResponse((
         Status::Ok,
-        Json(ResMe {
-            id: user.id,
-            email: u.email,
-            firstname: u.firstname,
-            lastname: u.lastname,
-        }),
+        "Verification email sent.".to_string(),
     )))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{auth::Role, entities::auth_event, testing};
+    use rocket::{futures::future::join_all, http::ContentType, serde::json::json};
+
+    #[rocket::async_test]
+    async fn concurrent_failed_sign_ins_lock_the_account() {
+        let app = testing::app(|config| {
+            config.login_max_failures = 5;
+            config.ip_max_failures = 100;
+        })
+        .await;
+        let u = testing::create_user(&app.db, "reader@example.com", Role::User).await;
+
+        let body = json!({"email": "reader@example.com", "password": "wrong password"}).to_string();
+        let responses = join_all((0..5).map(|_| {
+            app.client
+                .post("/auth/sign-in")
+                .remote("127.0.0.1:8000".parse().unwrap())
+                .header(ContentType::JSON)
+                .body(&body)
+                .dispatch()
+        }))
+        .await;
+        for res in responses {
+            assert_eq!(res.status(), Status::Unauthorized);
+        }
+
+        let u = User::find_by_id(u.id).one(&app.db).await.unwrap().unwrap();
+        assert!(u.locked_until.is_some());
+        assert_eq!(u.failed_logins, 0);
+
+        let locks = AuthEvent::find()
+            .filter(auth_event::Column::EventType.eq(EventType::AccountLocked.as_str()))
+            .count(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(locks, 1);
+    }
+
+    async fn forgot_password(app: &testing::TestApp, email: &str) -> Status {
+        app.client
+            .post("/auth/forgot-password")
+            .header(ContentType::JSON)
+            .body(json!({ "email": email }).to_string())
+            .dispatch()
+            .await
+            .status()
+    }
+
+    #[rocket::async_test]
+    async fn forgot_password_succeeds_when_the_mail_cannot_be_sent() {
+        let app = testing::app(|config| config.outbox_dir = "/dev/null/outbox".to_string()).await;
+        testing::create_user(&app.db, "reader@example.com", Role::User).await;
+
+        assert_eq!(
+            forgot_password(&app, "reader@example.com").await,
+            Status::Ok
+        );
+    }
+
+    #[rocket::async_test]
+    async fn forgot_password_is_throttled_per_email() {
+        let app = testing::app(|config| config.reset_max_requests = 2).await;
+        testing::create_user(&app.db, "reader@example.com", Role::User).await;
+
+        for _ in 0..3 {
+            assert_eq!(
+                forgot_password(&app, "reader@example.com").await,
+                Status::Ok
+            );
+        }
+        assert_eq!(
+            forgot_password(&app, "Reader@Example.com").await,
+            Status::Ok
+        );
+        assert_eq!(app.outbox_len(), 2);
+    }
+
+    async fn sign_up(app: &testing::TestApp, email: &str) -> Status {
+        app.client
+            .post("/auth/sign-up")
+            .remote("127.0.0.1:8000".parse().unwrap())
+            .header(ContentType::JSON)
+            .body(json!({"email": email, "password": testing::PASSWORD}).to_string())
+            .dispatch()
+            .await
+            .status()
+    }
+
+    #[rocket::async_test]
+    async fn sign_up_succeeds_when_the_mail_cannot_be_sent() {
+        let app = testing::app(|config| config.outbox_dir = "/dev/null/outbox".to_string()).await;
+
+        assert_eq!(sign_up(&app, "reader@example.com").await, Status::Created);
+        let created = User::find()
+            .filter(user::Column::Email.eq("reader@example.com"))
+            .one(&app.db)
+            .await
+            .unwrap();
+        assert!(created.is_some());
+    }
+
+    #[rocket::async_test]
+    async fn concurrent_sign_ups_with_the_same_email_create_one_account() {
+        let app = testing::app(|_| {}).await;
+
+        let statuses = join_all((0..5).map(|_| sign_up(&app, "reader@example.com"))).await;
+
+        assert_eq!(
+            statuses.iter().filter(|s| **s == Status::Created).count(),
+            1
+        );
+        assert!(statuses
+            .iter()
+            .all(|s| *s == Status::Created || *s == Status::UnprocessableEntity));
+    }
+}
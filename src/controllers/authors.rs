@@ -1,6 +1,5 @@
 use rocket::{
-    futures::future::ok,
-    http::Status,
+    http::{uri::Origin, Status},
     serde::{json::Json, Deserialize, Serialize},
     State,
 };
@@ -8,11 +7,13 @@ use sea_orm::{prelude::DateTimeUtc, *};
 use std::time::SystemTime;
 
 use super::{
-    books::{ResBook, ResBookList},
+    books::{credited_to, BookFilter, ResBook, ResBookList},
+    pagination::{paginate, parse_sort, PageMeta, PageQuery, Paged},
     ErrorResponse, Response, SuccessResponse,
 };
-use crate::auth::AuthenticatedUser;
-use crate::entities::{author, book, prelude::*};
+use crate::auth::{AuthenticatedUser, WriterUser};
+use crate::entities::{author, book, book_contributor, prelude::*};
+use crate::search::SearchIndex;
 
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
@@ -37,7 +38,8 @@ impl From<&author::Model> for ResAuthor {
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct ResAuthorList {
-    total: usize,
+    #[serde(flatten)]
+    meta: PageMeta,
     authors: Vec<ResAuthor>,
 }
 
@@ -49,34 +51,73 @@ pub struct ReqAuthor {
     bio: String,
 }
 
-#[get("/")]
-pub async fn index(db: &State<DatabaseConnection>) -> Response<Json<ResAuthorList>> {
+// 作者列表的过滤和排序参数
+#[derive(FromForm)]
+pub struct AuthorFilter {
+    firstname: Option<String>,
+    lastname: Option<String>,
+    sort: Option<String>,
+}
+
+// 允许排序的字段
+const AUTHOR_SORT: [(&str, author::Column); 5] = [
+    ("id", author::Column::Id),
+    ("firstname", author::Column::Firstname),
+    ("lastname", author::Column::Lastname),
+    ("created_at", author::Column::CreatedAt),
+    ("updated_at", author::Column::UpdatedAt),
+];
+
+#[get("/?<filter..>")]
+pub async fn index(
+    db: &State<DatabaseConnection>,
+    uri: &Origin<'_>,
+    paging: PageQuery,
+    filter: AuthorFilter,
+) -> Response<Paged<Json<ResAuthorList>>> {
     let db = db as &DatabaseConnection;
 
-    let authors = Author::find()
-        .order_by_desc(author::Column::UpdatedAt)
-        .all(db)
-        .await?
-        .iter()
-        .map(ResAuthor::from)
-        .collect::<Vec<_>>();
+    let mut select = Author::find();
+    if let Some(firstname) = filter.firstname.as_ref().filter(|n| !n.is_empty()) {
+        select = select.filter(author::Column::Firstname.eq(firstname));
+    }
+    if let Some(lastname) = filter.lastname.as_ref().filter(|n| !n.is_empty()) {
+        select = select.filter(author::Column::Lastname.eq(lastname));
+    }
+
+    let mut order = parse_sort(&filter.sort, &AUTHOR_SORT)?;
+    if order.is_empty() {
+        order.push((author::Column::UpdatedAt, Order::Desc));
+    } else if paging.is_cursor() {
+        return Err(ErrorResponse((
+            Status::UnprocessableEntity,
+            "sort cannot be combined with cursor pagination.".to_string(),
+        )));
+    }
+
+    let page = paginate(db, select, order, author::Column::Id, &paging, uri).await?;
 
     Ok(SuccessResponse((
         Status::Ok,
-        Json(ResAuthorList {
-            total: authors.len(),
-            authors,
-        }),
+        Paged(
+            Json(ResAuthorList {
+                meta: page.meta,
+                authors: page.items.iter().map(ResAuthor::from).collect(),
+            }),
+            page.link,
+        ),
     )))
 }
 
 #[post("/", data = "<req_author>")]
 pub async fn create(
     db: &State<DatabaseConnection>,
-    user: AuthenticatedUser,
+    search: &State<SearchIndex>,
+    user: WriterUser,
     req_author: Json<ReqAuthor>,
 ) -> Response<Json<ResAuthor>> {
     let db = db as &DatabaseConnection;
+    user.ensure_scope("authors:write")?;
 
     let author = author::ActiveModel {
         user_id: Set(user.id),
@@ -87,6 +128,7 @@ pub async fn create(
     };
 
     let author = author.insert(db).await?;
+    search.sync_author(db, author.id).await;
 
     Ok(SuccessResponse((
         Status::Created,
@@ -97,10 +139,11 @@ pub async fn create(
 #[get("/<id>")]
 pub async fn show(
     db: &State<DatabaseConnection>,
-    _user: AuthenticatedUser,
+    user: AuthenticatedUser,
     id: i32,
 ) -> Response<Json<ResAuthor>> {
     let db = db as &DatabaseConnection;
+    user.ensure_scope("authors:read")?;
 
     let author = Author::find_by_id(id).one(db).await?;
 
@@ -123,16 +166,21 @@ pub async fn show(
 #[put("/<id>", data = "<req_author>")]
 pub async fn update(
     db: &State<DatabaseConnection>,
-    _user: AuthenticatedUser,
+    search: &State<SearchIndex>,
+    user: WriterUser,
     id: i32,
     req_author: Json<ReqAuthor>,
 ) -> Response<Json<ResAuthor>> {
     let db = db as &DatabaseConnection;
+    user.ensure_scope("authors:write")?;
 
     let author = Author::find_by_id(id).one(db).await?;
 
     let mut author: author::ActiveModel = match author {
-        Some(a) => a.into(),
+        Some(a) => {
+            user.ensure_owner(a.user_id)?;
+            a.into()
+        }
         None => {
             return Err(ErrorResponse((
                 Status::NotFound,
@@ -145,9 +193,10 @@ pub async fn update(
     author.lastname = Set(req_author.lastname.to_owned());
     author.bio = Set(req_author.bio.to_owned());
 
-    author.updated_at = Set(Some((DateTimeUtc::from(SystemTime::now()))));
+    author.updated_at = Set(Some(DateTimeUtc::from(SystemTime::now())));
 
     let author = author.update(db).await?;
+    search.sync_author(db, author.id).await;
 
     Ok(SuccessResponse((
         Status::Ok,
@@ -158,10 +207,12 @@ pub async fn update(
 #[delete("/<id>")]
 pub async fn delete(
     db: &State<DatabaseConnection>,
-    _user: AuthenticatedUser,
+    search: &State<SearchIndex>,
+    user: WriterUser,
     id: i32,
 ) -> Response<String> {
     let db = db as &DatabaseConnection;
+    user.ensure_scope("authors:write")?;
 
     let author = match Author::find_by_id(id).one(db).await? {
         Some(a) => a,
@@ -173,36 +224,68 @@ pub async fn delete(
         }
     };
 
+    user.ensure_owner(author.user_id)?;
+
+    let credited = BookContributor::find()
+        .filter(book_contributor::Column::AuthorId.eq(author.id))
+        .count(db)
+        .await?;
+    if credited > 0 {
+        return Err(ErrorResponse((
+            Status::Conflict,
+            format!(
+                "The author is credited on {} book(s), remove them from those books first.",
+                credited
+            ),
+        )));
+    }
+
     author.delete(db).await?;
+    search.remove(&[], &[id]).await;
 
     Ok(SuccessResponse((Status::Ok, "Author deleted.".to_string())))
 }
 
-#[get("/<id>/books")]
+#[get("/<id>/books?<filter..>")]
 pub async fn get_books(
     db: &State<DatabaseConnection>,
-    _user: AuthenticatedUser,
+    user: AuthenticatedUser,
+    uri: &Origin<'_>,
     id: i32,
-) -> Response<Json<ResBookList>> {
+    paging: PageQuery,
+    filter: BookFilter,
+) -> Response<Paged<Json<ResBookList>>> {
     let db = db as &DatabaseConnection;
+    user.ensure_scope("books:read")?;
 
-    let author = match Author::find_by_id(id).one(db).await? {
-        Some(a) => a,
-        None => {
-            return Err(ErrorResponse((
-                Status::NotFound,
-                "No author found with the specified ID.".to_string(),
-            )));
-        }
-    };
+    if Author::find_by_id(id).one(db).await?.is_none() {
+        return Err(ErrorResponse((
+            Status::NotFound,
+            "No author found with the specified ID.".to_string(),
+        )));
+    }
 
-    let books: Vec<book::Model> = author.find_related(Book).all(db).await?;
+    // 包括作者以编者、译者等身份署名的书籍
+    let page = paginate(
+        db,
+        filter
+            .apply(db, Book::find().filter(credited_to(id)))
+            .await?,
+        filter.order(&paging)?,
+        book::Column::Id,
+        &paging,
+        uri,
+    )
+    .await?;
 
     Ok(SuccessResponse((
         Status::Ok,
-        Json(ResBookList {
-            total: books.len(),
-            books: books.iter().map(ResBook::from).collect::<Vec<_>>(),
-        }),
+        Paged(
+            Json(ResBookList {
+                meta: page.meta,
+                books: ResBook::load(db, &page.items).await?,
+            }),
+            page.link,
+        ),
     )))
 }
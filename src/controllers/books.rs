@@ -1,27 +1,62 @@
 use rocket::{
-    http::Status,
-    response::status,
+    http::{uri::Origin, Status},
     serde::{json::Json, Deserialize, Serialize},
-    time::format_description::modifier::Year,
     State,
 };
-use sea_orm::{prelude::DateTimeUtc, *};
-use std::time::SystemTime;
+use sea_orm::{
+    prelude::DateTimeUtc,
+    sea_query::{Alias, Expr, Query, SimpleExpr},
+    *,
+};
+use std::{collections::HashMap, time::SystemTime};
+
+use super::{
+    genres::descendants,
+    pagination::{escape_like, paginate, parse_param, parse_sort, PageMeta, PageQuery, Paged},
+    tags::normalize_tag,
+    ErrorResponse, Response, SuccessResponse,
+};
+use crate::auth::{AuthenticatedUser, WriterUser};
+
+use crate::entities::{
+    author, book, book_contributor, book_genre, book_tag, genre, prelude::*, tag,
+};
+use crate::isbn::Isbn;
+use crate::search::SearchIndex;
 
-use super::{ErrorResponse, Response, SuccessResponse};
-use crate::auth::AuthenticatedUser;
-use sea_orm::*;
+// 署名角色
+const CONTRIBUTOR_ROLES: [&str; 4] = ["author", "editor", "translator", "illustrator"];
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResContributor {
+    pub author_id: i32,
+    pub firstname: String,
+    pub lastname: String,
+    pub role: String,
+    pub position: i32,
+}
 
-use crate::entities::{book, prelude::*};
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResBookGenre {
+    pub id: i32,
+    pub name: String,
+}
 
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct ResBook {
     pub id: i32,
-    pub author_id: i32,
+    pub author_id: i32, // 第一位署名作者
     pub title: String,
     pub year: String,
     pub cover: String,
+    pub isbn13: Option<String>,
+    pub isbn10: Option<String>,
+    pub contributors: Vec<ResContributor>,
+    pub genres: Vec<ResBookGenre>,
+    pub tags: Vec<String>,
 }
 
 impl From<&book::Model> for ResBook {
@@ -32,79 +67,492 @@ impl From<&book::Model> for ResBook {
             title: value.title.to_owned(),
             year: value.year.to_owned(),
             cover: value.cover.to_owned(),
+            isbn13: value.isbn13.to_owned(),
+            isbn10: value.isbn10.to_owned(),
+            contributors: vec![],
+            genres: vec![],
+            tags: vec![],
         }
     }
 }
 
+impl ResBook {
+    // 批量读取书籍的署名、分类和标签，署名按署名顺序排列
+    pub async fn load<C: ConnectionTrait>(
+        db: &C,
+        books: &[book::Model],
+    ) -> Result<Vec<ResBook>, DbErr> {
+        let mut contributors: HashMap<i32, Vec<ResContributor>> = HashMap::new();
+        let mut genres: HashMap<i32, Vec<ResBookGenre>> = HashMap::new();
+        let mut tags: HashMap<i32, Vec<String>> = HashMap::new();
+
+        if !books.is_empty() {
+            let rows = BookContributor::find()
+                .filter(book_contributor::Column::BookId.is_in(books.iter().map(|b| b.id)))
+                .order_by_asc(book_contributor::Column::Position)
+                .order_by_asc(book_contributor::Column::Id)
+                .find_also_related(Author)
+                .all(db)
+                .await?;
+
+            for (c, a) in rows {
+                contributors
+                    .entry(c.book_id)
+                    .or_default()
+                    .push(ResContributor {
+                        author_id: c.author_id,
+                        firstname: a
+                            .as_ref()
+                            .map(|a| a.firstname.to_owned())
+                            .unwrap_or_default(),
+                        lastname: a
+                            .as_ref()
+                            .map(|a| a.lastname.to_owned())
+                            .unwrap_or_default(),
+                        role: c.role,
+                        position: c.position,
+                    });
+            }
+
+            let rows = BookGenre::find()
+                .filter(book_genre::Column::BookId.is_in(books.iter().map(|b| b.id)))
+                .find_also_related(Genre)
+                .order_by_asc(genre::Column::Name)
+                .all(db)
+                .await?;
+            for (bg, g) in rows {
+                if let Some(g) = g {
+                    genres.entry(bg.book_id).or_default().push(ResBookGenre {
+                        id: g.id,
+                        name: g.name,
+                    });
+                }
+            }
+
+            let rows = BookTag::find()
+                .filter(book_tag::Column::BookId.is_in(books.iter().map(|b| b.id)))
+                .find_also_related(Tag)
+                .order_by_asc(tag::Column::Name)
+                .all(db)
+                .await?;
+            for (bt, t) in rows {
+                if let Some(t) = t {
+                    tags.entry(bt.book_id).or_default().push(t.name);
+                }
+            }
+        }
+
+        Ok(books
+            .iter()
+            .map(|b| {
+                let mut res = ResBook::from(b);
+                res.contributors = contributors.remove(&b.id).unwrap_or_default();
+                res.genres = genres.remove(&b.id).unwrap_or_default();
+                res.tags = tags.remove(&b.id).unwrap_or_default();
+                res
+            })
+            .collect())
+    }
+
+    async fn load_one<C: ConnectionTrait>(db: &C, book: &book::Model) -> Result<ResBook, DbErr> {
+        Ok(ResBook::load(db, std::slice::from_ref(book))
+            .await?
+            .remove(0))
+    }
+}
+
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct ResBookList {
-    pub total: usize,
+    #[serde(flatten)]
+    pub meta: PageMeta,
     pub books: Vec<ResBook>,
 }
 
 #[derive(Deserialize)]
 #[serde(crate = "rocket::serde")]
-pub struct ReqBook {
+pub struct ReqContributor {
     author_id: i32,
+    role: Option<String>, // 默认为author
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqBook {
+    author_id: Option<i32>, // 只有一位作者时可以只传author_id
+    contributors: Option<Vec<ReqContributor>>,
+    genre_ids: Option<Vec<i32>>, // 不传时保留原有分类
     title: String,
     year: String,
     cover: String,
+    isbn: Option<String>, // ISBN-10或ISBN-13，可带连字符；不传时保留原有ISBN，空字符串表示清除
+}
+
+// (ISBN-13, ISBN-10)，978以外的前缀没有ISBN-10
+type IsbnPair = (Option<String>, Option<String>);
+
+impl ReqBook {
+    // 校验并规范化ISBN，没有传isbn时返回None
+    fn isbn(&self) -> Result<Option<IsbnPair>, ErrorResponse> {
+        match self.isbn.as_deref().map(str::trim) {
+            Some("") => Ok(Some((None, None))),
+            Some(isbn) => {
+                let isbn = Isbn::parse(isbn)
+                    .map_err(|e| ErrorResponse((Status::UnprocessableEntity, e.to_string())))?;
+                Ok(Some((Some(isbn.to_isbn13()), isbn.to_isbn10())))
+            }
+            None => Ok(None),
+        }
+    }
+
+    // 署名列表按数组顺序排列，返回None表示请求中没有署名信息
+    fn contributors(&self) -> Result<Option<Vec<(i32, String)>>, ErrorResponse> {
+        let contributors = match (&self.contributors, self.author_id) {
+            (Some(list), _) => list
+                .iter()
+                .map(|c| {
+                    let role = c.role.as_deref().unwrap_or("author").trim().to_lowercase();
+                    (c.author_id, role)
+                })
+                .collect::<Vec<_>>(),
+            (None, Some(author_id)) => vec![(author_id, "author".to_string())],
+            (None, None) => return Ok(None),
+        };
+
+        let invalid = |msg: String| Err(ErrorResponse((Status::UnprocessableEntity, msg)));
+
+        if contributors.is_empty() {
+            return invalid("A book needs at least one contributor.".to_string());
+        }
+        for (i, (author_id, role)) in contributors.iter().enumerate() {
+            if !CONTRIBUTOR_ROLES.contains(&role.as_str()) {
+                return invalid(format!(
+                    "Invalid contributor role {}, allowed roles: {}.",
+                    role,
+                    CONTRIBUTOR_ROLES.join(", ")
+                ));
+            }
+            if contributors[..i].contains(&(*author_id, role.to_owned())) {
+                return invalid(format!("Author {} is listed twice as {}.", author_id, role));
+            }
+        }
+
+        Ok(Some(contributors))
+    }
+}
+
+// 书籍的author_id保存第一位作者，没有作者时为第一位署名者
+fn primary_author(contributors: &[(i32, String)]) -> i32 {
+    contributors
+        .iter()
+        .find(|(_, role)| role == "author")
+        .unwrap_or(&contributors[0])
+        .0
+}
+
+// 替换书籍的署名列表，署名的作者必须存在
+async fn save_contributors<C: ConnectionTrait>(
+    db: &C,
+    book_id: i32,
+    contributors: &[(i32, String)],
+) -> Result<(), ErrorResponse> {
+    let mut ids = contributors.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+    ids.sort();
+    ids.dedup();
+
+    let found = Author::find()
+        .filter(author::Column::Id.is_in(ids.clone()))
+        .count(db)
+        .await?;
+    if found != ids.len() as u64 {
+        return Err(ErrorResponse((
+            Status::UnprocessableEntity,
+            "Some contributors refer to authors that do not exist.".to_string(),
+        )));
+    }
+
+    BookContributor::delete_many()
+        .filter(book_contributor::Column::BookId.eq(book_id))
+        .exec(db)
+        .await?;
+    BookContributor::insert_many(
+        contributors
+            .iter()
+            .enumerate()
+            .map(|(i, (author_id, role))| book_contributor::ActiveModel {
+                book_id: Set(book_id),
+                author_id: Set(*author_id),
+                role: Set(role.to_owned()),
+                position: Set(i as i32),
+                ..Default::default()
+            }),
+    )
+    .exec(db)
+    .await?;
+
+    Ok(())
+}
+
+// 替换书籍的分类，分类必须存在
+async fn save_genres<C: ConnectionTrait>(
+    db: &C,
+    book_id: i32,
+    genre_ids: &[i32],
+) -> Result<(), ErrorResponse> {
+    let mut ids = genre_ids.to_vec();
+    ids.sort();
+    ids.dedup();
+
+    let found = Genre::find()
+        .filter(genre::Column::Id.is_in(ids.clone()))
+        .count(db)
+        .await?;
+    if found != ids.len() as u64 {
+        return Err(ErrorResponse((
+            Status::UnprocessableEntity,
+            "Some genres do not exist.".to_string(),
+        )));
+    }
+
+    BookGenre::delete_many()
+        .filter(book_genre::Column::BookId.eq(book_id))
+        .exec(db)
+        .await?;
+    if !ids.is_empty() {
+        BookGenre::insert_many(ids.iter().map(|genre_id| book_genre::ActiveModel {
+            book_id: Set(book_id),
+            genre_id: Set(*genre_id),
+            ..Default::default()
+        }))
+        .exec(db)
+        .await?;
+    }
+
+    Ok(())
+}
+
+// 作者参与署名的书籍
+pub fn credited_to(author_id: i32) -> SimpleExpr {
+    book::Column::Id.in_subquery(
+        Query::select()
+            .column(book_contributor::Column::BookId)
+            .from(BookContributor)
+            .and_where(book_contributor::Column::AuthorId.eq(author_id))
+            .to_owned(),
+    )
+}
+
+// 同一版本只能登记一次，由唯一索引兜底
+fn isbn_conflict(err: DbErr) -> ErrorResponse {
+    match err.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => ErrorResponse((
+            Status::Conflict,
+            "A book with that ISBN already exists.".to_string(),
+        )),
+        _ => err.into(),
+    }
+}
+
+// 书籍列表的过滤和排序参数
+#[derive(FromForm)]
+pub struct BookFilter {
+    author_id: Option<String>,
+    year_from: Option<String>,
+    year_to: Option<String>,
+    title_contains: Option<String>,
+    genre: Option<String>, // 分类ID，包括其下级分类
+    tag: Option<String>,   // 多个标签用逗号分隔，需全部匹配
+    sort: Option<String>,
+}
+
+// 允许排序的字段
+const BOOK_SORT: [(&str, book::Column); 6] = [
+    ("id", book::Column::Id),
+    ("title", book::Column::Title),
+    ("year", book::Column::Year),
+    ("author_id", book::Column::AuthorId),
+    ("created_at", book::Column::CreatedAt),
+    ("updated_at", book::Column::UpdatedAt),
+];
+
+// year是字符串列，过滤和排序都按数字比较
+fn year_number() -> SimpleExpr {
+    Expr::col(book::Column::Year).cast_as(Alias::new("SIGNED"))
 }
 
-#[get("/")]
+impl BookFilter {
+    pub async fn apply(
+        &self,
+        db: &DatabaseConnection,
+        mut select: Select<Book>,
+    ) -> Result<Select<Book>, ErrorResponse> {
+        if let Some(author_id) = parse_param::<i32>("author_id", &self.author_id)? {
+            select = select.filter(credited_to(author_id));
+        }
+        if let Some(year) = parse_param::<i32>("year_from", &self.year_from)? {
+            select = select.filter(Expr::expr(year_number()).gte(year));
+        }
+        if let Some(year) = parse_param::<i32>("year_to", &self.year_to)? {
+            select = select.filter(Expr::expr(year_number()).lte(year));
+        }
+        if let Some(title) = self.title_contains.as_ref().filter(|t| !t.is_empty()) {
+            select = select.filter(book::Column::Title.like(format!("%{}%", escape_like(title))));
+        }
+        if let Some(genre_id) = parse_param::<i32>("genre", &self.genre)? {
+            select = select.filter(
+                book::Column::Id.in_subquery(
+                    Query::select()
+                        .column(book_genre::Column::BookId)
+                        .from(BookGenre)
+                        .and_where(
+                            book_genre::Column::GenreId.is_in(descendants(db, genre_id).await?),
+                        )
+                        .to_owned(),
+                ),
+            );
+        }
+        if let Some(tags) = self.tag.as_ref().filter(|t| !t.trim().is_empty()) {
+            for name in tags.split(',') {
+                select = select.filter(
+                    book::Column::Id.in_subquery(
+                        Query::select()
+                            .column(book_tag::Column::BookId)
+                            .from(BookTag)
+                            .inner_join(
+                                Tag,
+                                Expr::col((Tag, tag::Column::Id))
+                                    .equals((BookTag, book_tag::Column::TagId)),
+                            )
+                            .and_where(Expr::col((Tag, tag::Column::Name)).eq(normalize_tag(name)?))
+                            .to_owned(),
+                    ),
+                );
+            }
+        }
+
+        Ok(select)
+    }
+
+    // 未指定时按更新时间倒序
+    pub fn order(&self, paging: &PageQuery) -> Result<Vec<(SimpleExpr, Order)>, ErrorResponse> {
+        let order = parse_sort(&self.sort, &BOOK_SORT)?;
+
+        if order.is_empty() {
+            return Ok(vec![(
+                book::Column::UpdatedAt.into_simple_expr(),
+                Order::Desc,
+            )]);
+        }
+        if paging.is_cursor() {
+            return Err(ErrorResponse((
+                Status::UnprocessableEntity,
+                "sort cannot be combined with cursor pagination.".to_string(),
+            )));
+        }
+
+        Ok(order
+            .into_iter()
+            .map(|(col, ord)| match col {
+                book::Column::Year => (year_number(), ord),
+                col => (col.into_simple_expr(), ord),
+            })
+            .collect())
+    }
+}
+
+#[get("/?<filter..>")]
 pub async fn index(
     db: &State<DatabaseConnection>,
-    _user: AuthenticatedUser,
-) -> Response<Json<ResBookList>> {
+    user: AuthenticatedUser,
+    uri: &Origin<'_>,
+    paging: PageQuery,
+    filter: BookFilter,
+) -> Response<Paged<Json<ResBookList>>> {
     let db = db as &DatabaseConnection;
+    user.ensure_scope("books:read")?;
 
-    let books = Book::find()
-        .order_by_desc(book::Column::UpdatedAt)
-        .all(db)
-        .await?
-        .iter()
-        .map(ResBook::from)
-        .collect::<Vec<_>>();
+    let page = paginate(
+        db,
+        filter.apply(db, Book::find()).await?,
+        filter.order(&paging)?,
+        book::Column::Id,
+        &paging,
+        uri,
+    )
+    .await?;
 
     Ok(SuccessResponse((
         Status::Ok,
-        Json(ResBookList {
-            total: books.len(),
-            books,
-        }),
+        Paged(
+            Json(ResBookList {
+                meta: page.meta,
+                books: ResBook::load(db, &page.items).await?,
+            }),
+            page.link,
+        ),
     )))
 }
 
 #[post("/", data = "<req_book>")]
 pub async fn create(
     db: &State<DatabaseConnection>,
-    user: AuthenticatedUser,
+    search: &State<SearchIndex>,
+    user: WriterUser,
     req_book: Json<ReqBook>,
 ) -> Response<Json<ResBook>> {
     let db = db as &DatabaseConnection;
+    user.ensure_scope("books:write")?;
+
+    let (isbn13, isbn10) = req_book.isbn()?.unwrap_or_default();
+    let contributors = match req_book.contributors()? {
+        Some(contributors) => contributors,
+        None => {
+            return Err(ErrorResponse((
+                Status::UnprocessableEntity,
+                "Either author_id or contributors is required.".to_string(),
+            )));
+        }
+    };
+
+    let txn = db.begin().await?;
 
     let book = book::ActiveModel {
         user_id: Set(user.id),
-        author_id: Set(req_book.author_id),
+        author_id: Set(primary_author(&contributors)),
         title: Set(req_book.title.to_owned()),
         year: Set(req_book.year.to_owned()),
         cover: Set(req_book.cover.to_owned()),
+        isbn13: Set(isbn13),
+        isbn10: Set(isbn10),
         ..Default::default()
     };
 
-    let book = book.insert(db).await?;
+    let book = book.insert(&txn).await.map_err(isbn_conflict)?;
+    save_contributors(&txn, book.id, &contributors).await?;
+    save_genres(
+        &txn,
+        book.id,
+        req_book.genre_ids.as_deref().unwrap_or_default(),
+    )
+    .await?;
 
-    Ok(SuccessResponse((Status::Ok, Json(ResBook::from(&book)))))
+    txn.commit().await?;
+    search.sync_book(db, book.id).await;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResBook::load_one(db, &book).await?),
+    )))
 }
 
 #[get("/<id>")]
 pub async fn show(
     db: &State<DatabaseConnection>,
-    _user: AuthenticatedUser,
+    user: AuthenticatedUser,
     id: i32,
 ) -> Response<Json<ResBook>> {
     let db = db as &DatabaseConnection;
+    user.ensure_scope("books:read")?;
 
     let book = Book::find_by_id(id).one(db).await?;
 
@@ -118,20 +566,63 @@ pub async fn show(
         }
     };
 
-    Ok(SuccessResponse((Status::Ok, Json(ResBook::from(&book)))))
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResBook::load_one(db, &book).await?),
+    )))
+}
+
+// 按ISBN查找，ISBN-10和ISBN-13均可
+#[get("/isbn/<isbn>")]
+pub async fn show_by_isbn(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+    isbn: &str,
+) -> Response<Json<ResBook>> {
+    let db = db as &DatabaseConnection;
+    user.ensure_scope("books:read")?;
+
+    let isbn = Isbn::parse(isbn)
+        .map_err(|e| ErrorResponse((Status::UnprocessableEntity, e.to_string())))?;
+
+    let book = match Book::find()
+        .filter(book::Column::Isbn13.eq(isbn.to_isbn13()))
+        .one(db)
+        .await?
+    {
+        Some(book) => book,
+        None => {
+            return Err(ErrorResponse((
+                Status::NotFound,
+                "Cannot find a book with the specified ISBN.".to_string(),
+            )));
+        }
+    };
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResBook::load_one(db, &book).await?),
+    )))
 }
 
 #[put("/<id>", data = "<req_book>")]
 pub async fn update(
     db: &State<DatabaseConnection>,
-    _user: AuthenticatedUser,
+    search: &State<SearchIndex>,
+    user: WriterUser,
     id: i32,
     req_book: Json<ReqBook>,
 ) -> Response<Json<ResBook>> {
     let db = db as &DatabaseConnection;
+    user.ensure_scope("books:write")?;
+    let isbn = req_book.isbn()?;
+    let contributors = req_book.contributors()?;
 
     let mut book: book::ActiveModel = match Book::find_by_id(id).one(db).await? {
-        Some(b) => b.into(),
+        Some(b) => {
+            user.ensure_owner(b.user_id)?;
+            b.into()
+        }
         None => {
             return Err(ErrorResponse((
                 Status::NotFound,
@@ -143,21 +634,45 @@ pub async fn update(
     book.title = Set(req_book.title.to_owned());
     book.year = Set(req_book.year.to_owned());
     book.cover = Set(req_book.cover.to_owned());
+    // 没有传的ISBN、署名和分类都保留原有的值
+    if let Some((isbn13, isbn10)) = isbn {
+        book.isbn13 = Set(isbn13);
+        book.isbn10 = Set(isbn10);
+    }
+    if let Some(contributors) = &contributors {
+        book.author_id = Set(primary_author(contributors));
+    }
 
-    book.updated_at = Set(Some((DateTimeUtc::from(SystemTime::now()))));
+    book.updated_at = Set(Some(DateTimeUtc::from(SystemTime::now())));
 
-    let book = book.update(db).await?;
+    let txn = db.begin().await?;
 
-    Ok(SuccessResponse((Status::Ok, Json(ResBook::from(&book)))))
+    let book = book.update(&txn).await.map_err(isbn_conflict)?;
+    if let Some(contributors) = &contributors {
+        save_contributors(&txn, book.id, contributors).await?;
+    }
+    if let Some(genre_ids) = &req_book.genre_ids {
+        save_genres(&txn, book.id, genre_ids).await?;
+    }
+
+    txn.commit().await?;
+    search.sync_book(db, book.id).await;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResBook::load_one(db, &book).await?),
+    )))
 }
 
 #[delete("/<id>")]
 pub async fn delete(
     db: &State<DatabaseConnection>,
-    _user: AuthenticatedUser,
+    search: &State<SearchIndex>,
+    user: WriterUser,
     id: i32,
 ) -> Response<String> {
     let db = db as &DatabaseConnection;
+    user.ensure_scope("books:write")?;
 
     let book = match Book::find_by_id(id).one(db).await? {
         Some(b) => b,
@@ -169,7 +684,26 @@ pub async fn delete(
         }
     };
 
-    book.delete(db).await?;
+    user.ensure_owner(book.user_id)?;
+
+    let txn = db.begin().await?;
+
+    BookContributor::delete_many()
+        .filter(book_contributor::Column::BookId.eq(book.id))
+        .exec(&txn)
+        .await?;
+    BookGenre::delete_many()
+        .filter(book_genre::Column::BookId.eq(book.id))
+        .exec(&txn)
+        .await?;
+    BookTag::delete_many()
+        .filter(book_tag::Column::BookId.eq(book.id))
+        .exec(&txn)
+        .await?;
+    book.delete(&txn).await?;
+
+    txn.commit().await?;
+    search.remove(&[id], &[]).await;
 
     Ok(SuccessResponse((Status::Ok, "book deleted.".to_string())))
 }
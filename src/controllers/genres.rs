@@ -0,0 +1,296 @@
+use std::{collections::HashMap, time::SystemTime};
+
+use rocket::{
+    http::Status,
+    serde::{json::Json, Deserialize, Serialize},
+    State,
+};
+use sea_orm::{prelude::DateTimeUtc, *};
+
+use super::{ErrorResponse, Response, SuccessResponse};
+use crate::auth::{AuthenticatedUser, EditorUser};
+use crate::entities::{book_genre, genre, prelude::*};
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResGenre {
+    id: i32,
+    parent_id: Option<i32>,
+    name: String,
+    description: Option<String>,
+}
+
+impl From<&genre::Model> for ResGenre {
+    fn from(value: &genre::Model) -> Self {
+        Self {
+            id: value.id,
+            parent_id: value.parent_id,
+            name: value.name.to_owned(),
+            description: value.description.to_owned(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResGenreList {
+    total: usize,
+    genres: Vec<ResGenre>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResGenreDetail {
+    #[serde(flatten)]
+    genre: ResGenre,
+    ancestors: Vec<ResGenre>, // 从顶级分类开始
+    children: Vec<ResGenre>,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqGenre {
+    parent_id: Option<i32>,
+    name: String,
+    description: Option<String>,
+}
+
+// 分类数量不多，层级关系在内存中计算
+async fn parents(db: &DatabaseConnection) -> Result<HashMap<i32, Option<i32>>, DbErr> {
+    Ok(Genre::find()
+        .all(db)
+        .await?
+        .iter()
+        .map(|g| (g.id, g.parent_id))
+        .collect())
+}
+
+// 分类本身及其所有下级分类的ID
+pub async fn descendants(db: &DatabaseConnection, id: i32) -> Result<Vec<i32>, DbErr> {
+    let parents = parents(db).await?;
+
+    let mut ids = vec![id];
+    let mut i = 0;
+    while i < ids.len() {
+        let current = ids[i];
+        ids.extend(
+            parents
+                .iter()
+                .filter(|(_, parent)| **parent == Some(current))
+                .map(|(id, _)| *id),
+        );
+        i += 1;
+    }
+
+    Ok(ids)
+}
+
+fn not_found() -> ErrorResponse {
+    ErrorResponse((
+        Status::NotFound,
+        "Cannot find a genre with the specified ID.".to_string(),
+    ))
+}
+
+impl ReqGenre {
+    // 校验名称和上级分类，id为正在修改的分类
+    async fn validate(
+        &self,
+        db: &DatabaseConnection,
+        id: Option<i32>,
+    ) -> Result<String, ErrorResponse> {
+        let name = self.name.trim().to_string();
+        if name.is_empty() || name.chars().count() > 64 {
+            return Err(ErrorResponse((
+                Status::UnprocessableEntity,
+                "The genre name must be between 1 and 64 characters.".to_string(),
+            )));
+        }
+
+        if let Some(parent_id) = self.parent_id {
+            if Genre::find_by_id(parent_id).one(db).await?.is_none() {
+                return Err(ErrorResponse((
+                    Status::UnprocessableEntity,
+                    "The parent genre does not exist.".to_string(),
+                )));
+            }
+            // 不能移动到自身或自身的下级分类之下
+            if let Some(id) = id {
+                if descendants(db, id).await?.contains(&parent_id) {
+                    return Err(ErrorResponse((
+                        Status::UnprocessableEntity,
+                        "A genre cannot be moved under itself or one of its descendants."
+                            .to_string(),
+                    )));
+                }
+            }
+        }
+
+        // 同一上级分类下名称不能重复
+        let mut siblings = Genre::find().filter(genre::Column::Name.eq(&name));
+        siblings = match self.parent_id {
+            Some(parent_id) => siblings.filter(genre::Column::ParentId.eq(parent_id)),
+            None => siblings.filter(genre::Column::ParentId.is_null()),
+        };
+        if let Some(id) = id {
+            siblings = siblings.filter(genre::Column::Id.ne(id));
+        }
+        if siblings.count(db).await? > 0 {
+            return Err(ErrorResponse((
+                Status::Conflict,
+                "A genre with that name already exists at this level.".to_string(),
+            )));
+        }
+
+        Ok(name)
+    }
+}
+
+#[get("/")]
+pub async fn index(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+) -> Response<Json<ResGenreList>> {
+    let db = db as &DatabaseConnection;
+    user.ensure_scope("books:read")?;
+
+    let genres = Genre::find()
+        .order_by_asc(genre::Column::Name)
+        .all(db)
+        .await?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResGenreList {
+            total: genres.len(),
+            genres: genres.iter().map(ResGenre::from).collect(),
+        }),
+    )))
+}
+
+#[get("/<id>")]
+pub async fn show(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+    id: i32,
+) -> Response<Json<ResGenreDetail>> {
+    let db = db as &DatabaseConnection;
+    user.ensure_scope("books:read")?;
+
+    let genres = Genre::find()
+        .order_by_asc(genre::Column::Name)
+        .all(db)
+        .await?;
+    let by_id = genres.iter().map(|g| (g.id, g)).collect::<HashMap<_, _>>();
+
+    let g = by_id.get(&id).ok_or_else(not_found)?;
+
+    let mut ancestors = vec![];
+    let mut parent_id = g.parent_id;
+    while let Some(parent) = parent_id.and_then(|id| by_id.get(&id)) {
+        ancestors.insert(0, ResGenre::from(*parent));
+        parent_id = parent.parent_id;
+    }
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResGenreDetail {
+            genre: ResGenre::from(*g),
+            ancestors,
+            children: genres
+                .iter()
+                .filter(|c| c.parent_id == Some(id))
+                .map(ResGenre::from)
+                .collect(),
+        }),
+    )))
+}
+
+#[post("/", data = "<req_genre>")]
+pub async fn create(
+    db: &State<DatabaseConnection>,
+    user: EditorUser,
+    req_genre: Json<ReqGenre>,
+) -> Response<Json<ResGenre>> {
+    let db = db as &DatabaseConnection;
+    user.ensure_scope("books:write")?;
+
+    let name = req_genre.validate(db, None).await?;
+
+    let g = genre::ActiveModel {
+        parent_id: Set(req_genre.parent_id),
+        name: Set(name),
+        description: Set(req_genre.description.to_owned()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    Ok(SuccessResponse((Status::Created, Json(ResGenre::from(&g)))))
+}
+
+#[put("/<id>", data = "<req_genre>")]
+pub async fn update(
+    db: &State<DatabaseConnection>,
+    user: EditorUser,
+    id: i32,
+    req_genre: Json<ReqGenre>,
+) -> Response<Json<ResGenre>> {
+    let db = db as &DatabaseConnection;
+    user.ensure_scope("books:write")?;
+
+    let mut g: genre::ActiveModel = Genre::find_by_id(id)
+        .one(db)
+        .await?
+        .ok_or_else(not_found)?
+        .into();
+
+    let name = req_genre.validate(db, Some(id)).await?;
+
+    g.parent_id = Set(req_genre.parent_id);
+    g.name = Set(name);
+    g.description = Set(req_genre.description.to_owned());
+    g.updated_at = Set(Some(DateTimeUtc::from(SystemTime::now())));
+
+    let g = g.update(db).await?;
+
+    Ok(SuccessResponse((Status::Ok, Json(ResGenre::from(&g)))))
+}
+
+// 有下级分类或书籍时不能删除
+#[delete("/<id>")]
+pub async fn delete(db: &State<DatabaseConnection>, user: EditorUser, id: i32) -> Response<String> {
+    let db = db as &DatabaseConnection;
+    user.ensure_scope("books:write")?;
+
+    let g = Genre::find_by_id(id).one(db).await?.ok_or_else(not_found)?;
+
+    let children = Genre::find()
+        .filter(genre::Column::ParentId.eq(id))
+        .count(db)
+        .await?;
+    if children > 0 {
+        return Err(ErrorResponse((
+            Status::Conflict,
+            "The genre has sub-genres, move or delete them first.".to_string(),
+        )));
+    }
+
+    let books = BookGenre::find()
+        .filter(book_genre::Column::GenreId.eq(id))
+        .count(db)
+        .await?;
+    if books > 0 {
+        return Err(ErrorResponse((
+            Status::Conflict,
+            format!(
+                "The genre is assigned to {} book(s), remove it from those books first.",
+                books
+            ),
+        )));
+    }
+
+    g.delete(db).await?;
+
+    Ok(SuccessResponse((Status::Ok, "Genre deleted.".to_string())))
+}
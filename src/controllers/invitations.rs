@@ -0,0 +1,147 @@
+use rocket::{
+    http::Status,
+    serde::{json::Json, Deserialize, Serialize},
+    State,
+};
+use sea_orm::{prelude::DateTimeUtc, *};
+
+use super::{api_keys::expires_after_days, ErrorResponse, Response, SuccessResponse};
+use crate::auth::{generate_token, hash_token, AdminUser, Role};
+use crate::entities::{invitation, prelude::*};
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResInvitation {
+    id: i32,
+    role: String,
+    created_by: Option<i32>,
+    used_by: Option<i32>,
+    used_at: Option<DateTimeUtc>,
+    expires_at: Option<DateTimeUtc>,
+    created_at: Option<DateTimeUtc>,
+}
+
+impl From<&invitation::Model> for ResInvitation {
+    fn from(value: &invitation::Model) -> Self {
+        Self {
+            id: value.id,
+            role: value.role.to_owned(),
+            created_by: value.created_by,
+            used_by: value.used_by,
+            used_at: value.used_at,
+            expires_at: value.expires_at,
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResInvitationList {
+    total: usize,
+    invitations: Vec<ResInvitation>,
+}
+
+// 创建时返回邀请码，之后无法再查看
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResInvitationCreated {
+    code: String,
+    invitation: ResInvitation,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqInvitation {
+    role: Option<String>,
+    expires_in_days: Option<u64>,
+}
+
+#[get("/")]
+pub async fn index(
+    db: &State<DatabaseConnection>,
+    _admin: AdminUser,
+) -> Response<Json<ResInvitationList>> {
+    let db = db as &DatabaseConnection;
+
+    let invitations = Invitation::find()
+        .order_by_desc(invitation::Column::Id)
+        .all(db)
+        .await?
+        .iter()
+        .map(ResInvitation::from)
+        .collect::<Vec<_>>();
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResInvitationList {
+            total: invitations.len(),
+            invitations,
+        }),
+    )))
+}
+
+#[post("/", data = "<req_invitation>")]
+pub async fn create(
+    db: &State<DatabaseConnection>,
+    admin: AdminUser,
+    req_invitation: Json<ReqInvitation>,
+) -> Response<Json<ResInvitationCreated>> {
+    let db = db as &DatabaseConnection;
+
+    let role = match &req_invitation.role {
+        Some(role) => match role.parse::<Role>() {
+            Ok(r) => r,
+            Err(e) => return Err(ErrorResponse((Status::UnprocessableEntity, e))),
+        },
+        None => Role::User,
+    };
+
+    let code = generate_token(12);
+
+    let invitation = invitation::ActiveModel {
+        code_hash: Set(hash_token(&code)),
+        role: Set(role.to_string()),
+        created_by: Set(Some(admin.id)),
+        expires_at: Set(expires_after_days(req_invitation.expires_in_days)?),
+        ..Default::default()
+    };
+
+    let invitation = invitation.insert(db).await?;
+
+    Ok(SuccessResponse((
+        Status::Created,
+        Json(ResInvitationCreated {
+            code,
+            invitation: ResInvitation::from(&invitation),
+        }),
+    )))
+}
+
+// 删除未使用的邀请码
+#[delete("/<id>")]
+pub async fn delete(
+    db: &State<DatabaseConnection>,
+    _admin: AdminUser,
+    id: i32,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+
+    let res = Invitation::delete_many()
+        .filter(invitation::Column::Id.eq(id))
+        .filter(invitation::Column::UsedAt.is_null())
+        .exec(db)
+        .await?;
+
+    if res.rows_affected == 0 {
+        return Err(ErrorResponse((
+            Status::NotFound,
+            "No unused invitation with the specified ID.".to_string(),
+        )));
+    }
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        "Invitation deleted.".to_string(),
+    )))
+}
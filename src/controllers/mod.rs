@@ -1,9 +1,20 @@
-use rocket::http::Status;
+use rocket::http::{Header, Status};
 use sea_orm::DbErr;
 
+pub mod admin;
+pub mod api_keys;
 pub mod auth;
 pub mod authors;
 pub mod books;
+pub mod genres;
+pub mod invitations;
+pub mod oidc;
+pub mod pagination;
+pub mod profile;
+pub mod search;
+pub mod tags;
+pub mod two_factor;
+pub mod well_known;
 
 #[derive(Responder)]
 pub struct SuccessResponse<T>(pub (Status, T));
@@ -18,4 +29,45 @@ impl From<DbErr> for ErrorResponse {
     fn from(err: DbErr) -> Self {
         ErrorResponse((Status::InternalServerError, err.to_string()))
     }
-}
\ No newline at end of file
+}
+
+// 请求过于频繁，附带Retry-After头
+#[derive(Responder)]
+#[response(status = 429)]
+pub struct TooManyRequests(pub String, pub Header<'static>);
+
+impl TooManyRequests {
+    pub fn retry_after(secs: u64) -> Self {
+        TooManyRequests(
+            "Too many attempts, please try again later.".to_string(),
+            Header::new("Retry-After", secs.to_string()),
+        )
+    }
+}
+
+// 可能被限流的接口使用的错误类型
+#[derive(Responder)]
+pub enum LimitedError {
+    Error(ErrorResponse),
+    TooManyRequests(TooManyRequests),
+}
+
+pub type LimitedResponse<T> = Result<SuccessResponse<T>, LimitedError>;
+
+impl From<ErrorResponse> for LimitedError {
+    fn from(err: ErrorResponse) -> Self {
+        LimitedError::Error(err)
+    }
+}
+
+impl From<DbErr> for LimitedError {
+    fn from(err: DbErr) -> Self {
+        LimitedError::Error(err.into())
+    }
+}
+
+impl From<TooManyRequests> for LimitedError {
+    fn from(err: TooManyRequests) -> Self {
+        LimitedError::TooManyRequests(err)
+    }
+}
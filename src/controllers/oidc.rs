@@ -0,0 +1,338 @@
+use std::time::SystemTime;
+
+use rocket::{
+    http::{Cookie, CookieJar, SameSite, Status},
+    response::Redirect,
+    serde::json::Json,
+    time::Duration,
+    State,
+};
+use sea_orm::{prelude::DateTimeUtc, *};
+
+use super::{
+    auth::{ensure_active, ensure_not_locked, issue_tokens, two_factor_challenge, ResSignInStep},
+    ErrorResponse, LimitedResponse, SuccessResponse,
+};
+use crate::auth::{
+    audit::{ClientInfo, EventType},
+    generate_token,
+    keys::KeyStore,
+    oidc::{FlowState, IdTokenClaims, OidcProvider},
+    password::hash_password,
+};
+use crate::entities::{prelude::*, user, user_identity};
+use crate::{AppConfig, RegistrationMode};
+
+const FLOW_COOKIE: &str = "oidc_flow";
+
+fn provider(provider: &Option<OidcProvider>) -> Result<&OidcProvider, ErrorResponse> {
+    provider.as_ref().ok_or(ErrorResponse((
+        Status::NotFound,
+        "OIDC sign-in is not configured.".to_string(),
+    )))
+}
+
+// 跳转到身份提供方的登录页
+#[get("/oidc/login")]
+pub async fn login(
+    config: &State<AppConfig>,
+    keys: &State<KeyStore>,
+    oidc: &State<Option<OidcProvider>>,
+    cookies: &CookieJar<'_>,
+) -> Result<Redirect, ErrorResponse> {
+    let oidc = provider(oidc)?;
+
+    let flow = FlowState::new(config.challenge_token_ttl);
+    let url = oidc
+        .authorize_url(&flow)
+        .await
+        .map_err(|e| ErrorResponse((Status::BadGateway, e)))?;
+
+    cookies.add(
+        Cookie::build((FLOW_COOKIE, flow.encode(keys)))
+            .path("/auth/oidc")
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .max_age(Duration::seconds(config.challenge_token_ttl as i64)),
+    );
+
+    Ok(Redirect::to(url))
+}
+
+// 身份提供方回调时附带的查询参数
+#[derive(FromForm)]
+pub struct CallbackParams {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+#[get("/oidc/callback?<params..>")]
+pub async fn callback(
+    db: &State<DatabaseConnection>,
+    config: &State<AppConfig>,
+    keys: &State<KeyStore>,
+    oidc: &State<Option<OidcProvider>>,
+    cookies: &CookieJar<'_>,
+    info: ClientInfo,
+    params: CallbackParams,
+) -> LimitedResponse<Json<ResSignInStep>> {
+    let db = db as &DatabaseConnection;
+    let config = config as &AppConfig;
+    let oidc = provider(oidc)?;
+
+    let flow = cookies
+        .get(FLOW_COOKIE)
+        .and_then(|c| FlowState::decode(keys, c.value()));
+    cookies.remove(Cookie::build(FLOW_COOKIE).path("/auth/oidc"));
+
+    if let Some(error) = params.error {
+        return Err(ErrorResponse((
+            Status::Unauthorized,
+            format!("The identity provider returned an error: {}", error),
+        ))
+        .into());
+    }
+
+    // state必须与发起登录时保存的一致，防止CSRF
+    let (flow, code) = match (flow, params.code) {
+        (Some(flow), Some(code)) if params.state.as_deref() == Some(flow.state.as_str()) => {
+            (flow, code)
+        }
+        _ => {
+            return Err(ErrorResponse((
+                Status::BadRequest,
+                "Invalid or expired sign-in request.".to_string(),
+            ))
+            .into());
+        }
+    };
+
+    let claims = oidc
+        .exchange(&code, &flow)
+        .await
+        .map_err(|e| ErrorResponse((Status::Unauthorized, e)))?;
+
+    let u = find_or_provision(db, config, oidc, &claims).await?;
+
+    // 与密码登录相同的账号检查
+    ensure_not_locked(db, &info, &u).await?;
+    ensure_active(db, config, &info, &u).await?;
+
+    // 身份提供方的登录不能代替本站的两步验证
+    if u.totp_enabled_at.is_some() {
+        return Ok(SuccessResponse((
+            Status::Ok,
+            Json(two_factor_challenge(config, keys, &u)),
+        )));
+    }
+
+    let res = issue_tokens(db, config, keys, &u, None).await?;
+    info.record(db, Some(u.id), EventType::OidcSignIn, None)
+        .await;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResSignInStep::Tokens(res)),
+    )))
+}
+
+// 按已关联的身份查找用户；首次登录时关联同邮箱的账号，或自动创建账号
+async fn find_or_provision(
+    db: &DatabaseConnection,
+    config: &AppConfig,
+    oidc: &OidcProvider,
+    claims: &IdTokenClaims,
+) -> Result<user::Model, ErrorResponse> {
+    let now = DateTimeUtc::from(SystemTime::now());
+
+    if let Some(identity) = UserIdentity::find()
+        .filter(user_identity::Column::Issuer.eq(&oidc.issuer))
+        .filter(user_identity::Column::Subject.eq(&claims.sub))
+        .one(db)
+        .await?
+    {
+        let user_id = identity.user_id;
+        let mut identity: user_identity::ActiveModel = identity.into();
+        identity.email = Set(claims.email.to_owned());
+        identity.last_login_at = Set(Some(now));
+        identity.update(db).await?;
+
+        return Ok(User::find_by_id(user_id).one(db).await?.unwrap());
+    }
+
+    let email = match &claims.email {
+        Some(email) => email,
+        None => {
+            return Err(ErrorResponse((
+                Status::Forbidden,
+                "The identity provider did not return an email address.".to_string(),
+            )));
+        }
+    };
+    let email_verified = claims.email_verified.unwrap_or(false);
+
+    let u = match User::find()
+        .filter(user::Column::Email.eq(email))
+        .one(db)
+        .await?
+    {
+        // 只有提供方确认过邮箱时才关联已有账号，避免账号被接管
+        Some(_) if !email_verified => {
+            return Err(ErrorResponse((
+                Status::Conflict,
+                "An account exists with that email address, but the identity provider has not verified it.".to_string(),
+            )));
+        }
+        Some(u) => u,
+        None if !config.oidc_auto_provision => {
+            return Err(ErrorResponse((
+                Status::Forbidden,
+                "No account is linked to this identity.".to_string(),
+            )));
+        }
+        // 身份提供方登录无法携带邀请码，只有开放注册时才自动创建账号
+        None if config.registration_mode != RegistrationMode::Open => {
+            return Err(ErrorResponse((
+                Status::Forbidden,
+                "Registration is not open; no account is linked to this identity.".to_string(),
+            )));
+        }
+        None => {
+            // 自动创建的账号没有可用的密码，只能通过身份提供方或重置密码登录
+            let res = User::insert(user::ActiveModel {
+                email: Set(email.to_owned()),
+                password: Set(hash_password(&generate_token(32))?),
+                firstname: Set(claims.given_name.to_owned()),
+                lastname: Set(claims.family_name.to_owned()),
+                verified_at: Set(if email_verified { Some(now) } else { None }),
+                ..Default::default()
+            })
+            .exec(db)
+            .await?;
+
+            User::find_by_id(res.last_insert_id).one(db).await?.unwrap()
+        }
+    };
+
+    UserIdentity::insert(user_identity::ActiveModel {
+        user_id: Set(u.id),
+        issuer: Set(oidc.issuer.to_owned()),
+        subject: Set(claims.sub.to_owned()),
+        email: Set(claims.email.to_owned()),
+        last_login_at: Set(Some(now)),
+        ..Default::default()
+    })
+    .exec(db)
+    .await?;
+
+    Ok(u)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        auth::Role,
+        testing::{self, MockIdp, TestApp},
+        UnverifiedPolicy,
+    };
+    use rocket::serde::json::{json, Value};
+
+    async fn app(idp: &MockIdp, configure: impl FnOnce(&mut AppConfig)) -> TestApp {
+        let issuer = idp.issuer.to_owned();
+        testing::app(|config| {
+            config.oidc_issuer = Some(issuer);
+            config.oidc_client_id = Some("bookstore".to_string());
+            configure(config);
+        })
+        .await
+    }
+
+    // 走一遍/auth/oidc/login到callback的完整流程，身份提供方返回email对应的身份
+    async fn sign_in(app: &TestApp, idp: &MockIdp, email: &str, verified: bool) -> (Status, Value) {
+        let res = app.client.get("/auth/oidc/login").dispatch().await;
+        assert_eq!(res.status(), Status::SeeOther);
+        let location = res.headers().get_one("Location").unwrap().to_string();
+        let flow = res.cookies().get(FLOW_COOKIE).unwrap().clone();
+
+        let url = reqwest::Url::parse(&location).unwrap();
+        assert!(url
+            .as_str()
+            .starts_with(&format!("{}/authorize", idp.issuer)));
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.to_string())
+                .unwrap()
+        };
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        idp.set_claims(json!({
+            "iss": idp.issuer,
+            "aud": "bookstore",
+            "sub": format!("idp|{}", email),
+            "iat": now,
+            "exp": now + 300,
+            "nonce": param("nonce"),
+            "email": email,
+            "email_verified": verified,
+        }));
+
+        let res = app
+            .client
+            .get(format!(
+                "/auth/oidc/callback?code=idp-code&state={}",
+                param("state")
+            ))
+            .remote("127.0.0.1:8000".parse().unwrap())
+            .cookie(flow)
+            .dispatch()
+            .await;
+        let status = res.status();
+
+        (status, res.into_json().await.unwrap_or(Value::Null))
+    }
+
+    #[rocket::async_test]
+    async fn signs_in_through_the_identity_provider() {
+        let idp = MockIdp::start().await;
+        let app = app(&idp, |_| {}).await;
+
+        let (status, body) = sign_in(&app, &idp, "reader@example.com", true).await;
+        assert_eq!(status, Status::Ok);
+        assert!(body["token"].is_string());
+
+        let identities = UserIdentity::find().count(&app.db).await.unwrap();
+        assert_eq!(identities, 1);
+    }
+
+    #[rocket::async_test]
+    async fn a_locked_account_cannot_sign_in() {
+        let idp = MockIdp::start().await;
+        let app = app(&idp, |_| {}).await;
+        let u = testing::create_user(&app.db, "reader@example.com", Role::User).await;
+        let mut u: user::ActiveModel = u.into();
+        u.locked_until = Set(Some(DateTimeUtc::from(
+            SystemTime::now() + std::time::Duration::from_secs(600),
+        )));
+        u.update(&app.db).await.unwrap();
+
+        let (status, _) = sign_in(&app, &idp, "reader@example.com", true).await;
+        assert_eq!(status, Status::TooManyRequests);
+    }
+
+    #[rocket::async_test]
+    async fn an_unverified_email_is_blocked_when_the_policy_requires_it() {
+        let idp = MockIdp::start().await;
+        let app = app(&idp, |config| {
+            config.unverified_policy = UnverifiedPolicy::BlockSignIn;
+        })
+        .await;
+
+        let (status, _) = sign_in(&app, &idp, "reader@example.com", false).await;
+        assert_eq!(status, Status::Forbidden);
+    }
+}
@@ -0,0 +1,340 @@
+use std::str::FromStr;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rocket::{
+    http::{uri::Origin, Status},
+    request::{self, FromRequest, Outcome},
+    response::{self, Responder},
+    serde::Serialize,
+    Request,
+};
+use sea_orm::*;
+
+use super::ErrorResponse;
+
+const DEFAULT_PER_PAGE: u64 = 20;
+const MAX_PER_PAGE: u64 = 100;
+
+// 分页参数：page为页码分页，cursor为游标分页（按ID倒序，适合连续翻页，
+// 第一次请求传空的cursor=）。
+// 作为请求守卫读取，路由的查询参数可以留给过滤条件使用。
+pub struct PageQuery {
+    page: Option<u64>,
+    per_page: Option<u64>,
+    cursor: Option<String>,
+}
+
+impl PageQuery {
+    pub fn is_cursor(&self) -> bool {
+        self.cursor.is_some()
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for PageQuery {
+    type Error = String;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let page = req.query_value::<u64>("page").transpose();
+        let per_page = req.query_value::<u64>("per_page").transpose();
+
+        match (page, per_page) {
+            (Ok(page), Ok(per_page)) => Outcome::Success(PageQuery {
+                page,
+                per_page,
+                cursor: req.query_value::<String>("cursor").and_then(|c| c.ok()),
+            }),
+            _ => Outcome::Error((
+                Status::UnprocessableEntity,
+                "Invalid page or per_page.".to_string(),
+            )),
+        }
+    }
+}
+
+// 解析数字类型的过滤参数，格式错误时返回422而不是忽略
+pub fn parse_param<T: FromStr>(
+    name: &str,
+    value: &Option<String>,
+) -> Result<Option<T>, ErrorResponse> {
+    match value {
+        Some(v) => v.trim().parse().map(Some).map_err(|_| {
+            ErrorResponse((
+                Status::UnprocessableEntity,
+                format!("Invalid value for {}: {}", name, v),
+            ))
+        }),
+        None => Ok(None),
+    }
+}
+
+// 转义LIKE的通配符，用户输入的%和_按原字符匹配（MySQL默认的转义符为\）
+pub fn escape_like(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+// 解析排序参数，如sort=-year,title，"-"表示倒序，字段必须在白名单中
+pub fn parse_sort<C: Copy>(
+    sort: &Option<String>,
+    allowed: &[(&str, C)],
+) -> Result<Vec<(C, Order)>, ErrorResponse> {
+    let sort = match sort {
+        Some(s) if !s.trim().is_empty() => s,
+        _ => return Ok(vec![]),
+    };
+
+    sort.split(',')
+        .map(|field| {
+            let field = field.trim();
+            let (name, order) = match field.strip_prefix('-') {
+                Some(name) => (name, Order::Desc),
+                None => (field.strip_prefix('+').unwrap_or(field), Order::Asc),
+            };
+
+            allowed
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, col)| (*col, order))
+                .ok_or(ErrorResponse((
+                    Status::UnprocessableEntity,
+                    format!(
+                        "Cannot sort by {}, allowed fields: {}.",
+                        name,
+                        allowed
+                            .iter()
+                            .map(|(n, _)| *n)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                )))
+        })
+        .collect()
+}
+
+// 页码不能超过最后一页（没有数据时为第1页），也避免计算偏移量时溢出
+pub fn check_page(page: u64, total_pages: u64) -> Result<(), ErrorResponse> {
+    let last = total_pages.max(1);
+    if page > last {
+        return Err(ErrorResponse((
+            Status::UnprocessableEntity,
+            format!("Page {} is out of range, the last page is {}.", page, last),
+        )));
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PageMeta {
+    pub total: u64,
+    pub per_page: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_pages: Option<u64>,
+    pub next_cursor: Option<String>,
+}
+
+pub struct Page<M> {
+    pub items: Vec<M>,
+    pub meta: PageMeta,
+    pub link: Option<String>,
+}
+
+// 附带Link头（RFC 8288）的分页响应
+pub struct Paged<T>(pub T, pub Option<String>);
+
+impl<'r, 'o: 'r, T: Responder<'r, 'o>> Responder<'r, 'o> for Paged<T> {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'o> {
+        let mut res = self.0.respond_to(req)?;
+        if let Some(link) = self.1 {
+            res.set_raw_header("Link", link);
+        }
+        Ok(res)
+    }
+}
+
+fn encode_cursor(id: i32) -> String {
+    URL_SAFE_NO_PAD.encode(id.to_string())
+}
+
+fn decode_cursor(cursor: &str) -> Result<i32, ErrorResponse> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|b| String::from_utf8(b).ok())
+        .and_then(|s| s.parse().ok())
+        .ok_or(ErrorResponse((
+            Status::UnprocessableEntity,
+            "Invalid cursor.".to_string(),
+        )))
+}
+
+// 保留其他查询参数，替换分页参数
+fn link(uri: &Origin<'_>, params: &str, rel: &str) -> String {
+    let mut query = uri
+        .query()
+        .map(|q| {
+            q.raw_segments()
+                .filter(|s| {
+                    let key = s.as_str().split('=').next().unwrap_or_default();
+                    !["page", "per_page", "cursor"].contains(&key)
+                })
+                .map(|s| s.as_str().to_string())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    query.push(params.to_string());
+
+    format!("<{}?{}>; rel=\"{}\"", uri.path(), query.join("&"), rel)
+}
+
+// 按分页参数查询，total使用COUNT统计。
+// select不要带排序：页码分页使用order并以ID倒序兜底，游标分页固定按ID倒序。
+// order可以是列，也可以是表达式。
+pub async fn paginate<E, O>(
+    db: &DatabaseConnection,
+    select: Select<E>,
+    order: Vec<(O, Order)>,
+    id: E::Column,
+    query: &PageQuery,
+    uri: &Origin<'_>,
+) -> Result<Page<E::Model>, ErrorResponse>
+where
+    E: EntityTrait,
+    E::Model: FromQueryResult + ModelTrait<Entity = E> + Sync + Send,
+    O: IntoSimpleExpr,
+{
+    let per_page = query
+        .per_page
+        .unwrap_or(DEFAULT_PER_PAGE)
+        .clamp(1, MAX_PER_PAGE);
+
+    let total = select.clone().count(db).await?;
+
+    if let Some(cursor) = &query.cursor {
+        let mut select = select;
+        if !cursor.is_empty() {
+            select = select.filter(id.lt(decode_cursor(cursor)?));
+        }
+
+        let mut items = select.order_by_desc(id).limit(per_page + 1).all(db).await?;
+
+        let next_cursor = if items.len() as u64 > per_page {
+            items.truncate(per_page as usize);
+            match items.last().map(|m| m.get(id)) {
+                Some(Value::Int(Some(last))) => Some(encode_cursor(last)),
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        let link = next_cursor
+            .as_ref()
+            .map(|c| link(uri, &format!("cursor={}&per_page={}", c, per_page), "next"));
+
+        return Ok(Page {
+            items,
+            meta: PageMeta {
+                total,
+                per_page,
+                page: None,
+                total_pages: None,
+                next_cursor,
+            },
+            link,
+        });
+    }
+
+    let page = query.page.unwrap_or(1).max(1);
+    let total_pages = total.div_ceil(per_page);
+    check_page(page, total_pages)?;
+
+    let mut select = select;
+    for (col, ord) in order {
+        select = select.order_by(col, ord);
+    }
+    let items = select
+        .order_by_desc(id)
+        .paginate(db, per_page)
+        .fetch_page(page - 1)
+        .await?;
+
+    let mut links = vec![link(uri, &format!("page=1&per_page={}", per_page), "first")];
+    if page > 1 {
+        links.push(link(
+            uri,
+            &format!("page={}&per_page={}", page - 1, per_page),
+            "prev",
+        ));
+    }
+    if page < total_pages {
+        links.push(link(
+            uri,
+            &format!("page={}&per_page={}", page + 1, per_page),
+            "next",
+        ));
+    }
+    links.push(link(
+        uri,
+        &format!("page={}&per_page={}", total_pages.max(1), per_page),
+        "last",
+    ));
+
+    Ok(Page {
+        items,
+        meta: PageMeta {
+            total,
+            per_page,
+            page: Some(page),
+            total_pages: Some(total_pages),
+            next_cursor: None,
+        },
+        link: Some(links.join(", ")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{auth::Role, testing};
+    use rocket::http::Header;
+
+    #[test]
+    fn accepts_pages_up_to_the_last_one() {
+        assert!(check_page(1, 0).is_ok());
+        assert!(check_page(3, 3).is_ok());
+    }
+
+    #[test]
+    fn rejects_pages_past_the_last_one() {
+        assert!(check_page(2, 0).is_err());
+        assert!(check_page(4, 3).is_err());
+        assert!(check_page(u64::MAX, 3).is_err());
+    }
+
+    #[rocket::async_test]
+    async fn a_huge_page_number_is_rejected() {
+        let app = testing::app(|_| {}).await;
+        testing::create_user(&app.db, "reader@example.com", Role::User).await;
+        let token = testing::sign_in(&app.client, "reader@example.com").await;
+
+        let res = app
+            .client
+            .get(format!("/books?page={}", u64::MAX))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch()
+            .await;
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+    }
+}
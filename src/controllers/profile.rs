@@ -0,0 +1,570 @@
+use std::time::{Duration, SystemTime};
+
+use rocket::{
+    http::{Header, Status},
+    serde::{json::Json, Deserialize, Serialize},
+    State,
+};
+use sea_orm::{prelude::DateTimeUtc, sea_query::Expr, *};
+
+use super::{
+    auth::{issue_tokens, revoke_all_sessions, ResMe, ResSignIn},
+    authors::ResAuthor,
+    books::ResBook,
+    ErrorResponse, Response, SuccessResponse,
+};
+use crate::auth::{
+    audit::{ClientInfo, EventType},
+    generate_token, hash_token,
+    keys::KeyStore,
+    password::{hash_password, verify_password, PasswordPolicy},
+    SessionUser,
+};
+use crate::entities::{
+    api_key, auth_event, author, book, book_contributor, book_genre, book_tag, email_change,
+    email_verification_token, invitation, password_reset_token, prelude::*, recovery_code,
+    refresh_token, revoked_token, tag, user, user_identity,
+};
+use crate::mailer::{Mail, Mailer};
+use crate::search::SearchIndex;
+use crate::AppConfig;
+
+// 校验当前密码，修改敏感信息前使用
+fn check_password(u: &user::Model, password: &str) -> Result<(), ErrorResponse> {
+    if verify_password(password, &u.password) {
+        Ok(())
+    } else {
+        Err(ErrorResponse((
+            Status::Forbidden,
+            "The current password is incorrect.".to_string(),
+        )))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqUpdateMe {
+    firstname: Option<String>,
+    lastname: Option<String>,
+}
+
+#[patch("/me", data = "<req_update>")]
+pub async fn update_me(
+    db: &State<DatabaseConnection>,
+    user: SessionUser,
+    req_update: Json<ReqUpdateMe>,
+) -> Response<Json<ResMe>> {
+    let db = db as &DatabaseConnection;
+
+    let mut u: user::ActiveModel = User::find_by_id(user.id).one(db).await?.unwrap().into();
+
+    if let Some(firstname) = &req_update.firstname {
+        u.firstname = Set(Some(firstname.to_owned()));
+    }
+    if let Some(lastname) = &req_update.lastname {
+        u.lastname = Set(Some(lastname.to_owned()));
+    }
+    u.updated_at = Set(Some(DateTimeUtc::from(SystemTime::now())));
+
+    let u = u.update(db).await?;
+
+    Ok(SuccessResponse((Status::Ok, Json(ResMe::from(&u)))))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqChangePassword {
+    current_password: String,
+    new_password: String,
+}
+
+// 修改密码后其他会话全部失效，为当前会话签发新令牌
+#[post("/change-password", data = "<req_change>")]
+pub async fn change_password(
+    db: &State<DatabaseConnection>,
+    config: &State<AppConfig>,
+    keys: &State<KeyStore>,
+    policy: &State<PasswordPolicy>,
+    info: ClientInfo,
+    user: SessionUser,
+    req_change: Json<ReqChangePassword>,
+) -> Response<Json<ResSignIn>> {
+    let db = db as &DatabaseConnection;
+
+    let u = User::find_by_id(user.id).one(db).await?.unwrap();
+    check_password(&u, &req_change.current_password)?;
+    policy.check(&req_change.new_password)?;
+
+    let mut u: user::ActiveModel = u.into();
+    u.password = Set(hash_password(&req_change.new_password)?);
+    u.updated_at = Set(Some(DateTimeUtc::from(SystemTime::now())));
+    u.update(db).await?;
+
+    revoke_all_sessions(db, user.id).await?;
+    info.record(db, Some(user.id), EventType::PasswordChanged, None)
+        .await;
+
+    // 重新读取用户以取得新的会话版本
+    let u = User::find_by_id(user.id).one(db).await?.unwrap();
+    let res = issue_tokens(db, config, keys, &u, None).await?;
+
+    Ok(SuccessResponse((Status::Ok, Json(res))))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqChangeEmail {
+    new_email: String,
+    password: String,
+}
+
+// 向新邮箱发送确认链接，确认后才真正修改
+#[post("/change-email", data = "<req_change>")]
+pub async fn change_email(
+    db: &State<DatabaseConnection>,
+    config: &State<AppConfig>,
+    mailer: &State<Box<dyn Mailer>>,
+    user: SessionUser,
+    req_change: Json<ReqChangeEmail>,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+    let config = config as &AppConfig;
+
+    let u = User::find_by_id(user.id).one(db).await?.unwrap();
+    check_password(&u, &req_change.password)?;
+
+    if User::find()
+        .filter(user::Column::Email.eq(&req_change.new_email))
+        .one(db)
+        .await?
+        .is_some()
+    {
+        return Err(ErrorResponse((
+            Status::UnprocessableEntity,
+            "An account exists with that email address.".to_string(),
+        )));
+    }
+
+    EmailChange::delete_many()
+        .filter(email_change::Column::UserId.eq(user.id))
+        .exec(db)
+        .await?;
+
+    let token = generate_token(32);
+
+    EmailChange::insert(email_change::ActiveModel {
+        user_id: Set(user.id),
+        new_email: Set(req_change.new_email.to_owned()),
+        token_hash: Set(hash_token(&token)),
+        expires_at: Set(DateTimeUtc::from(
+            SystemTime::now() + Duration::from_secs(config.verify_token_ttl),
+        )),
+        ..Default::default()
+    })
+    .exec(db)
+    .await?;
+
+    mailer
+        .send(Mail {
+            to: req_change.new_email.to_owned(),
+            subject: "Confirm your new BookStore email address".to_string(),
+            body: format!(
+                "Use the link below to confirm your new email address.\n\n{}/confirm-email?token={}\n",
+                config.app_url, token
+            ),
+        })
+        .await
+        .map_err(|e| ErrorResponse((Status::InternalServerError, e)))?;
+
+    Ok(SuccessResponse((
+        Status::Accepted,
+        "A confirmation link has been sent to the new email address.".to_string(),
+    )))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqConfirmEmail {
+    token: String,
+}
+
+#[post("/change-email/confirm", data = "<req_confirm>")]
+pub async fn confirm_email_change(
+    db: &State<DatabaseConnection>,
+    mailer: &State<Box<dyn Mailer>>,
+    req_confirm: Json<ReqConfirmEmail>,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+    let now = DateTimeUtc::from(SystemTime::now());
+
+    let change = match EmailChange::find()
+        .filter(email_change::Column::TokenHash.eq(hash_token(&req_confirm.token)))
+        .filter(email_change::Column::ExpiresAt.gt(now))
+        .one(db)
+        .await?
+    {
+        Some(c) => c,
+        None => {
+            return Err(ErrorResponse((
+                Status::BadRequest,
+                "Invalid or expired confirmation token.".to_string(),
+            )));
+        }
+    };
+
+    let u = User::find_by_id(change.user_id).one(db).await?.unwrap();
+    let old_email = u.email.to_owned();
+
+    let mut u: user::ActiveModel = u.into();
+    u.email = Set(change.new_email.to_owned());
+    u.verified_at = Set(Some(now));
+    u.updated_at = Set(Some(now));
+
+    // 确认期间邮箱可能已被他人注册，由唯一索引兜底
+    if let Err(err) = u.update(db).await {
+        return Err(match err.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => ErrorResponse((
+                Status::UnprocessableEntity,
+                "An account exists with that email address.".to_string(),
+            )),
+            _ => err.into(),
+        });
+    }
+
+    change.delete(db).await?;
+
+    // 通知旧邮箱，便于发现账号被盗用
+    mailer
+        .send(Mail {
+            to: old_email,
+            subject: "Your BookStore email address was changed".to_string(),
+            body: "The email address on your BookStore account has been changed. If you did not make this change, please contact support.\n".to_string(),
+        })
+        .await
+        .map_err(|e| ErrorResponse((Status::InternalServerError, e)))?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        "Email address changed.".to_string(),
+    )))
+}
+
+// 以附件形式下载
+#[derive(Responder)]
+pub struct Attachment<T>(pub T, pub Header<'static>);
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResExport {
+    profile: ResMe,
+    created_at: Option<DateTimeUtc>,
+    authors: Vec<ResAuthor>,
+    books: Vec<ResBook>,
+}
+
+// 导出用户资料及其创建的所有作者和书籍
+#[get("/me/export")]
+pub async fn export(
+    db: &State<DatabaseConnection>,
+    user: SessionUser,
+) -> Response<Attachment<Json<ResExport>>> {
+    let db = db as &DatabaseConnection;
+
+    let u = User::find_by_id(user.id).one(db).await?.unwrap();
+
+    let authors = Author::find()
+        .filter(author::Column::UserId.eq(user.id))
+        .order_by_asc(author::Column::Id)
+        .all(db)
+        .await?
+        .iter()
+        .map(ResAuthor::from)
+        .collect::<Vec<_>>();
+
+    let books = Book::find()
+        .filter(book::Column::UserId.eq(user.id))
+        .order_by_asc(book::Column::Id)
+        .all(db)
+        .await?;
+    let books = ResBook::load(db, &books).await?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Attachment(
+            Json(ResExport {
+                profile: ResMe::from(&u),
+                created_at: u.created_at,
+                authors,
+                books,
+            }),
+            Header::new(
+                "Content-Disposition",
+                format!("attachment; filename=\"bookstore-user-{}.json\"", user.id),
+            ),
+        ),
+    )))
+}
+
+// 注销账号时如何处理用户创建的作者和书籍
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum ContentAction {
+    Reassign,
+    Delete,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqDeleteMe {
+    password: String,
+    content: ContentAction,
+}
+
+#[delete("/me", data = "<req_delete>")]
+pub async fn delete_me(
+    db: &State<DatabaseConnection>,
+    config: &State<AppConfig>,
+    search: &State<SearchIndex>,
+    user: SessionUser,
+    req_delete: Json<ReqDeleteMe>,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+
+    let u = User::find_by_id(user.id).one(db).await?.unwrap();
+    check_password(&u, &req_delete.password)?;
+
+    // 其他用户注销时的内容会转给该账号，删除后无法再转移
+    if config.reassign_owner_id == Some(user.id) {
+        return Err(ErrorResponse((
+            Status::Conflict,
+            "This account receives content from deleted accounts and cannot be deleted."
+                .to_string(),
+        )));
+    }
+
+    let txn = db.begin().await?;
+
+    // 删除的书籍和作者，提交后从搜索索引中移除
+    let mut removed_books = vec![];
+    let mut removed_authors = vec![];
+
+    match req_delete.content {
+        ContentAction::Reassign => {
+            let owner_id = match config.reassign_owner_id {
+                Some(id) => id,
+                None => {
+                    return Err(ErrorResponse((
+                        Status::UnprocessableEntity,
+                        "Reassigning content is not available, please delete it instead."
+                            .to_string(),
+                    )));
+                }
+            };
+
+            // 启动时检查过，运行期间该账号仍可能被删除或停用
+            match User::find_by_id(owner_id).one(&txn).await? {
+                Some(owner) if owner.disabled_at.is_none() => {}
+                Some(_) => {
+                    return Err(ErrorResponse((
+                        Status::Conflict,
+                        "The account that receives reassigned content is disabled, please delete your content instead."
+                            .to_string(),
+                    )));
+                }
+                None => {
+                    return Err(ErrorResponse((
+                        Status::InternalServerError,
+                        "The account that receives reassigned content does not exist.".to_string(),
+                    )));
+                }
+            }
+
+            Book::update_many()
+                .col_expr(book::Column::UserId, Expr::value(owner_id))
+                .filter(book::Column::UserId.eq(user.id))
+                .exec(&txn)
+                .await?;
+            Author::update_many()
+                .col_expr(author::Column::UserId, Expr::value(owner_id))
+                .filter(author::Column::UserId.eq(user.id))
+                .exec(&txn)
+                .await?;
+        }
+        ContentAction::Delete => {
+            // 其他用户的书籍署名了该用户的作者时不能删除
+            let referenced = BookContributor::find()
+                .inner_join(Author)
+                .inner_join(Book)
+                .filter(author::Column::UserId.eq(user.id))
+                .filter(book::Column::UserId.ne(user.id))
+                .count(&txn)
+                .await?;
+            if referenced > 0 {
+                return Err(ErrorResponse((
+                    Status::Conflict,
+                    "Other users have books referencing your authors, please reassign your content instead.".to_string(),
+                )));
+            }
+
+            removed_books = Book::find()
+                .filter(book::Column::UserId.eq(user.id))
+                .all(&txn)
+                .await?
+                .iter()
+                .map(|b| b.id)
+                .collect();
+            removed_authors = Author::find()
+                .filter(author::Column::UserId.eq(user.id))
+                .all(&txn)
+                .await?
+                .iter()
+                .map(|a| a.id)
+                .collect();
+
+            BookContributor::delete_many()
+                .filter(book_contributor::Column::BookId.is_in(removed_books.clone()))
+                .exec(&txn)
+                .await?;
+            BookGenre::delete_many()
+                .filter(book_genre::Column::BookId.is_in(removed_books.clone()))
+                .exec(&txn)
+                .await?;
+            BookTag::delete_many()
+                .filter(book_tag::Column::BookId.is_in(removed_books.clone()))
+                .exec(&txn)
+                .await?;
+            Book::delete_many()
+                .filter(book::Column::UserId.eq(user.id))
+                .exec(&txn)
+                .await?;
+            Author::delete_many()
+                .filter(author::Column::UserId.eq(user.id))
+                .exec(&txn)
+                .await?;
+        }
+    }
+
+    // 外键没有级联删除，先清理账号相关的记录
+    // 审计记录、邀请码和标签保留，只解除与账号的关联
+    Tag::update_many()
+        .col_expr(tag::Column::UserId, Expr::value(Option::<i32>::None))
+        .filter(tag::Column::UserId.eq(user.id))
+        .exec(&txn)
+        .await?;
+    BookTag::update_many()
+        .col_expr(book_tag::Column::UserId, Expr::value(Option::<i32>::None))
+        .filter(book_tag::Column::UserId.eq(user.id))
+        .exec(&txn)
+        .await?;
+    Invitation::update_many()
+        .col_expr(
+            invitation::Column::CreatedBy,
+            Expr::value(Option::<i32>::None),
+        )
+        .filter(invitation::Column::CreatedBy.eq(user.id))
+        .exec(&txn)
+        .await?;
+    Invitation::update_many()
+        .col_expr(invitation::Column::UsedBy, Expr::value(Option::<i32>::None))
+        .filter(invitation::Column::UsedBy.eq(user.id))
+        .exec(&txn)
+        .await?;
+    AuthEvent::update_many()
+        .col_expr(auth_event::Column::UserId, Expr::value(Option::<i32>::None))
+        .filter(auth_event::Column::UserId.eq(user.id))
+        .exec(&txn)
+        .await?;
+    RefreshToken::delete_many()
+        .filter(refresh_token::Column::UserId.eq(user.id))
+        .exec(&txn)
+        .await?;
+    RevokedToken::delete_many()
+        .filter(revoked_token::Column::UserId.eq(user.id))
+        .exec(&txn)
+        .await?;
+    PasswordResetToken::delete_many()
+        .filter(password_reset_token::Column::UserId.eq(user.id))
+        .exec(&txn)
+        .await?;
+    EmailVerificationToken::delete_many()
+        .filter(email_verification_token::Column::UserId.eq(user.id))
+        .exec(&txn)
+        .await?;
+    EmailChange::delete_many()
+        .filter(email_change::Column::UserId.eq(user.id))
+        .exec(&txn)
+        .await?;
+    RecoveryCode::delete_many()
+        .filter(recovery_code::Column::UserId.eq(user.id))
+        .exec(&txn)
+        .await?;
+    ApiKey::delete_many()
+        .filter(api_key::Column::UserId.eq(user.id))
+        .exec(&txn)
+        .await?;
+    UserIdentity::delete_many()
+        .filter(user_identity::Column::UserId.eq(user.id))
+        .exec(&txn)
+        .await?;
+
+    User::delete_by_id(user.id).exec(&txn).await?;
+
+    txn.commit().await?;
+    search.remove(&removed_books, &removed_authors).await;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        "Account deleted.".to_string(),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        auth::Role,
+        testing::{self, TestApp},
+    };
+    use rocket::http::{ContentType, Header};
+
+    async fn delete_me(app: &TestApp, email: &str, content: &str) -> Status {
+        let token = testing::sign_in(&app.client, email).await;
+
+        app.client
+            .delete("/auth/me")
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(ContentType::JSON)
+            .body(
+                rocket::serde::json::json!({"password": testing::PASSWORD, "content": content})
+                    .to_string(),
+            )
+            .dispatch()
+            .await
+            .status()
+    }
+
+    #[rocket::async_test]
+    async fn content_is_not_reassigned_to_a_disabled_owner() {
+        let app = testing::app(|config| config.reassign_owner_id = Some(1)).await;
+        let owner = testing::create_user(&app.db, "owner@example.com", Role::Admin).await;
+        assert_eq!(owner.id, 1);
+        testing::create_user(&app.db, "reader@example.com", Role::User).await;
+
+        let mut owner: user::ActiveModel = owner.into();
+        owner.disabled_at = Set(Some(DateTimeUtc::from(SystemTime::now())));
+        owner.update(&app.db).await.unwrap();
+
+        let status = delete_me(&app, "reader@example.com", "reassign").await;
+        assert_eq!(status, Status::Conflict);
+    }
+
+    #[rocket::async_test]
+    async fn the_reassign_owner_cannot_delete_their_account() {
+        let app = testing::app(|config| config.reassign_owner_id = Some(1)).await;
+        let owner = testing::create_user(&app.db, "owner@example.com", Role::Admin).await;
+        assert_eq!(owner.id, 1);
+
+        let status = delete_me(&app, "owner@example.com", "delete").await;
+        assert_eq!(status, Status::Conflict);
+    }
+}
@@ -0,0 +1,145 @@
+use rocket::{
+    http::Status,
+    serde::{json::Json, Serialize},
+    State,
+};
+
+use super::{ErrorResponse, Response, SuccessResponse};
+use crate::auth::AuthenticatedUser;
+use crate::search::{SearchHit, SearchIndex};
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+// 每个词会在三个字段上展开为模糊查询，限制查询的长度和词数
+const MAX_QUERY_CHARS: usize = 200;
+const MAX_QUERY_WORDS: usize = 10;
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResSearchHit {
+    kind: String,
+    id: i32,
+    score: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    snippet: String, // 匹配的词用<b>标出
+}
+
+impl From<SearchHit> for ResSearchHit {
+    fn from(value: SearchHit) -> Self {
+        // 书籍的name是作者姓名，只有作者返回name，书籍只返回title
+        let (title, name) = match value.kind.as_str() {
+            "book" => (value.title, None),
+            _ => (None, value.name),
+        };
+
+        Self {
+            kind: value.kind,
+            id: value.id,
+            score: value.score,
+            title,
+            name,
+            snippet: value.snippet,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResSearch {
+    total: usize,
+    hits: Vec<ResSearchHit>,
+}
+
+// 按相关度搜索书籍和作者
+#[get("/?<q>&<limit>")]
+pub async fn search(
+    search: &State<SearchIndex>,
+    user: AuthenticatedUser,
+    q: Option<String>,
+    limit: Option<usize>,
+) -> Response<Json<ResSearch>> {
+    user.ensure_scope("books:read")?;
+
+    let q = match q.as_deref().map(str::trim) {
+        Some(q) if !q.is_empty() => q.to_string(),
+        _ => {
+            return Err(ErrorResponse((
+                Status::UnprocessableEntity,
+                "The search query q is required.".to_string(),
+            )));
+        }
+    };
+    if q.chars().count() > MAX_QUERY_CHARS {
+        return Err(ErrorResponse((
+            Status::UnprocessableEntity,
+            format!(
+                "The search query is too long, at most {} characters.",
+                MAX_QUERY_CHARS
+            ),
+        )));
+    }
+    if q.split_whitespace().count() > MAX_QUERY_WORDS {
+        return Err(ErrorResponse((
+            Status::UnprocessableEntity,
+            format!(
+                "The search query has too many words, at most {}.",
+                MAX_QUERY_WORDS
+            ),
+        )));
+    }
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let (total, hits) = search
+        .search(q, limit)
+        .await
+        .map_err(|e| ErrorResponse((Status::InternalServerError, e)))?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResSearch {
+            total,
+            hits: hits.into_iter().map(ResSearchHit::from).collect(),
+        }),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{auth::Role, testing};
+    use rocket::http::{Header, Status};
+
+    async fn search_status(q: &str) -> Status {
+        let app = testing::app(|_| {}).await;
+        testing::create_user(&app.db, "reader@example.com", Role::User).await;
+        let token = testing::sign_in(&app.client, "reader@example.com").await;
+
+        let res = app
+            .client
+            .get(format!("/search?q={}", q))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch()
+            .await;
+
+        res.status()
+    }
+
+    #[rocket::async_test]
+    async fn queries_within_the_caps_are_searched() {
+        assert_eq!(search_status("rust+book").await, Status::Ok);
+    }
+
+    #[rocket::async_test]
+    async fn overlong_queries_are_rejected() {
+        let q = "a".repeat(201);
+        assert_eq!(search_status(&q).await, Status::UnprocessableEntity);
+    }
+
+    #[rocket::async_test]
+    async fn queries_with_too_many_words_are_rejected() {
+        let q = ["word"; 11].join("+");
+        assert_eq!(search_status(&q).await, Status::UnprocessableEntity);
+    }
+}
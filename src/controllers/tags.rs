@@ -0,0 +1,387 @@
+use rocket::{
+    http::Status,
+    serde::{json::Json, Deserialize, Serialize},
+    State,
+};
+use sea_orm::{
+    sea_query::{Alias, Expr},
+    *,
+};
+
+use super::{books::BookFilter, pagination::escape_like, ErrorResponse, Response, SuccessResponse};
+use crate::auth::{AuthenticatedUser, WriterUser};
+use crate::entities::{book, book_tag, prelude::*, tag};
+
+const DEFAULT_LIMIT: u64 = 50;
+const MAX_LIMIT: u64 = 200;
+
+#[derive(Serialize, FromQueryResult)]
+#[serde(crate = "rocket::serde")]
+pub struct ResTagCount {
+    id: i32,
+    name: String,
+    count: i64, // 带有该标签的书籍数量
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResTagList {
+    tags: Vec<ResTagCount>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResTag {
+    id: i32,
+    name: String,
+}
+
+impl From<&tag::Model> for ResTag {
+    fn from(value: &tag::Model) -> Self {
+        Self {
+            id: value.id,
+            name: value.name.to_owned(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqTag {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqBookTags {
+    tags: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResBookTags {
+    book_id: i32,
+    tags: Vec<ResTag>,
+}
+
+// 标签统一为小写，连续空白合并为一个空格。
+// 逗号用于在过滤参数中分隔多个标签，不能出现在标签中。
+pub fn normalize_tag(name: &str) -> Result<String, ErrorResponse> {
+    let name = name
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+
+    if name.is_empty() || name.chars().count() > 32 || name.contains(',') {
+        return Err(ErrorResponse((
+            Status::UnprocessableEntity,
+            format!(
+                "Invalid tag \"{}\": tags must be 1 to 32 characters and cannot contain commas.",
+                name
+            ),
+        )));
+    }
+
+    Ok(name)
+}
+
+// 查找标签，不存在时创建
+async fn find_or_create(
+    db: &DatabaseConnection,
+    name: &str,
+    user_id: i32,
+) -> Result<tag::Model, DbErr> {
+    if let Some(t) = Tag::find()
+        .filter(tag::Column::Name.eq(name))
+        .one(db)
+        .await?
+    {
+        return Ok(t);
+    }
+
+    let res = tag::ActiveModel {
+        name: Set(name.to_owned()),
+        user_id: Set(Some(user_id)),
+        ..Default::default()
+    }
+    .insert(db)
+    .await;
+
+    match res {
+        Ok(t) => Ok(t),
+        // 并发请求已经创建了同名标签
+        Err(err) => match err.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => Tag::find()
+                .filter(tag::Column::Name.eq(name))
+                .one(db)
+                .await?
+                .ok_or(err),
+            _ => Err(err),
+        },
+    }
+}
+
+async fn book_tags(db: &DatabaseConnection, book_id: i32) -> Result<ResBookTags, DbErr> {
+    let tags = Tag::find()
+        .inner_join(BookTag)
+        .filter(book_tag::Column::BookId.eq(book_id))
+        .order_by_asc(tag::Column::Name)
+        .all(db)
+        .await?;
+
+    Ok(ResBookTags {
+        book_id,
+        tags: tags.iter().map(ResTag::from).collect(),
+    })
+}
+
+fn tag_not_found() -> ErrorResponse {
+    ErrorResponse((
+        Status::NotFound,
+        "Cannot find a tag with the specified ID.".to_string(),
+    ))
+}
+
+// 标签及其书籍数量，用于分面导航。
+// 统计范围与/books使用相同的过滤参数，q按前缀匹配标签名称。
+#[get("/?<q>&<limit>&<filter..>")]
+pub async fn index(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+    q: Option<String>,
+    limit: Option<u64>,
+    filter: BookFilter,
+) -> Response<Json<ResTagList>> {
+    let db = db as &DatabaseConnection;
+    user.ensure_scope("books:read")?;
+
+    let books = filter
+        .apply(db, Book::find())
+        .await?
+        .select_only()
+        .column(book::Column::Id)
+        .into_query();
+
+    let mut select = Tag::find()
+        .select_only()
+        .column(tag::Column::Id)
+        .column(tag::Column::Name)
+        .column_as(book_tag::Column::BookId.count(), "count")
+        .inner_join(BookTag)
+        .filter(book_tag::Column::BookId.in_subquery(books));
+    if let Some(q) = q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        select =
+            select.filter(tag::Column::Name.like(format!("{}%", escape_like(&q.to_lowercase()))));
+    }
+
+    let tags = select
+        .group_by(tag::Column::Id)
+        .group_by(tag::Column::Name)
+        .order_by_desc(Expr::col(Alias::new("count")))
+        .order_by_asc(tag::Column::Name)
+        .limit(limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT))
+        .into_model::<ResTagCount>()
+        .all(db)
+        .await?;
+
+    Ok(SuccessResponse((Status::Ok, Json(ResTagList { tags }))))
+}
+
+#[post("/", data = "<req_tag>")]
+pub async fn create(
+    db: &State<DatabaseConnection>,
+    user: WriterUser,
+    req_tag: Json<ReqTag>,
+) -> Response<Json<ResTag>> {
+    let db = db as &DatabaseConnection;
+    user.ensure_scope("books:write")?;
+
+    let name = normalize_tag(&req_tag.name)?;
+    let t = find_or_create(db, &name, user.id).await?;
+
+    Ok(SuccessResponse((Status::Created, Json(ResTag::from(&t)))))
+}
+
+// 编辑和管理员可以修改或删除任意标签。
+// 标签是共享的，创建者只能在没有其他用户使用该标签时修改或删除
+async fn ensure_tag_owner(
+    db: &DatabaseConnection,
+    user: &WriterUser,
+    t: &tag::Model,
+) -> Result<(), ErrorResponse> {
+    if user.is_elevated() {
+        return Ok(());
+    }
+
+    if t.user_id != Some(user.id) {
+        return Err(ErrorResponse((
+            Status::Forbidden,
+            "You do not have permission to modify this resource.".to_string(),
+        )));
+    }
+
+    let shared = BookTag::find()
+        .filter(book_tag::Column::TagId.eq(t.id))
+        .filter(
+            Condition::any()
+                .add(book_tag::Column::UserId.ne(user.id))
+                .add(book_tag::Column::UserId.is_null()),
+        )
+        .count(db)
+        .await?;
+    if shared > 0 {
+        return Err(ErrorResponse((
+            Status::Forbidden,
+            "The tag is used by other users and can only be changed by an editor.".to_string(),
+        )));
+    }
+
+    Ok(())
+}
+
+#[put("/<id>", data = "<req_tag>")]
+pub async fn update(
+    db: &State<DatabaseConnection>,
+    user: WriterUser,
+    id: i32,
+    req_tag: Json<ReqTag>,
+) -> Response<Json<ResTag>> {
+    let db = db as &DatabaseConnection;
+    user.ensure_scope("books:write")?;
+
+    let t = Tag::find_by_id(id)
+        .one(db)
+        .await?
+        .ok_or_else(tag_not_found)?;
+    ensure_tag_owner(db, &user, &t).await?;
+
+    let name = normalize_tag(&req_tag.name)?;
+    let mut t: tag::ActiveModel = t.into();
+    t.name = Set(name);
+
+    match t.update(db).await {
+        Ok(t) => Ok(SuccessResponse((Status::Ok, Json(ResTag::from(&t))))),
+        Err(err) => Err(match err.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => ErrorResponse((
+                Status::Conflict,
+                "A tag with that name already exists.".to_string(),
+            )),
+            _ => err.into(),
+        }),
+    }
+}
+
+// 删除标签时一并移除所有书籍上的该标签
+#[delete("/<id>")]
+pub async fn delete(db: &State<DatabaseConnection>, user: WriterUser, id: i32) -> Response<String> {
+    let db = db as &DatabaseConnection;
+    user.ensure_scope("books:write")?;
+
+    let t = Tag::find_by_id(id)
+        .one(db)
+        .await?
+        .ok_or_else(tag_not_found)?;
+    ensure_tag_owner(db, &user, &t).await?;
+
+    let txn = db.begin().await?;
+
+    BookTag::delete_many()
+        .filter(book_tag::Column::TagId.eq(id))
+        .exec(&txn)
+        .await?;
+    t.delete(&txn).await?;
+
+    txn.commit().await?;
+
+    Ok(SuccessResponse((Status::Ok, "Tag deleted.".to_string())))
+}
+
+// 给书籍添加标签，标签不存在时自动创建。所有可写用户都可以给任意书籍添加标签。
+#[post("/<id>/tags", data = "<req_tags>")]
+pub async fn tag_book(
+    db: &State<DatabaseConnection>,
+    user: WriterUser,
+    id: i32,
+    req_tags: Json<ReqBookTags>,
+) -> Response<Json<ResBookTags>> {
+    let db = db as &DatabaseConnection;
+    user.ensure_scope("books:write")?;
+
+    if Book::find_by_id(id).one(db).await?.is_none() {
+        return Err(ErrorResponse((
+            Status::NotFound,
+            "No book with the specified ID.".to_string(),
+        )));
+    }
+
+    let names = req_tags
+        .tags
+        .iter()
+        .map(|t| normalize_tag(t))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for name in names {
+        let t = find_or_create(db, &name, user.id).await?;
+
+        let res = BookTag::insert(book_tag::ActiveModel {
+            book_id: Set(id),
+            tag_id: Set(t.id),
+            user_id: Set(Some(user.id)),
+            ..Default::default()
+        })
+        .exec(db)
+        .await;
+
+        // 书籍已经有该标签
+        if let Err(err) = res {
+            if !matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) {
+                return Err(err.into());
+            }
+        }
+    }
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(book_tags(db, id).await?),
+    )))
+}
+
+// 添加标签的用户、书籍的创建者、编辑和管理员可以移除标签
+#[delete("/<id>/tags/<tag_id>")]
+pub async fn untag_book(
+    db: &State<DatabaseConnection>,
+    user: WriterUser,
+    id: i32,
+    tag_id: i32,
+) -> Response<Json<ResBookTags>> {
+    let db = db as &DatabaseConnection;
+    user.ensure_scope("books:write")?;
+
+    let (assignment, b) = match BookTag::find()
+        .filter(book_tag::Column::BookId.eq(id))
+        .filter(book_tag::Column::TagId.eq(tag_id))
+        .find_also_related(Book)
+        .one(db)
+        .await?
+    {
+        Some((assignment, Some(b))) => (assignment, b),
+        _ => {
+            return Err(ErrorResponse((
+                Status::NotFound,
+                "The book does not have the specified tag.".to_string(),
+            )));
+        }
+    };
+
+    if assignment.user_id != Some(user.id) {
+        user.ensure_owner(b.user_id)?;
+    }
+
+    assignment.delete(db).await?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(book_tags(db, id).await?),
+    )))
+}
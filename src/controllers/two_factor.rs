@@ -0,0 +1,408 @@
+use std::time::{Duration, SystemTime};
+
+use rocket::{
+    http::Status,
+    serde::{json::Json, Deserialize, Serialize},
+    State,
+};
+use sea_orm::{prelude::DateTimeUtc, sea_query::Expr, *};
+
+use super::{
+    auth::{count_failure, ensure_active, ensure_not_locked, issue_tokens, ResSignIn},
+    ErrorResponse, LimitedResponse, Response, SuccessResponse, TooManyRequests,
+};
+use crate::auth::{
+    audit::{ClientInfo, EventType},
+    generate_token, hash_token,
+    keys::KeyStore,
+    throttle::{ClientAddr, LoginThrottle},
+    totp, SessionUser,
+};
+use crate::entities::{prelude::*, recovery_code, revoked_token, user};
+use crate::AppConfig;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResEnroll {
+    secret: String,
+    otpauth_uri: String,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqCode {
+    code: String,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResRecoveryCodes {
+    recovery_codes: Vec<String>,
+}
+
+// 校验TOTP动态码，或消耗一个未使用的恢复码
+async fn check_second_factor(
+    db: &DatabaseConnection,
+    u: &user::Model,
+    code: &str,
+) -> Result<bool, DbErr> {
+    let secret = match &u.totp_secret {
+        Some(s) => s,
+        None => return Ok(false),
+    };
+
+    // 条件更新保证同一时间步的动态码只能使用一次
+    if let Some(step) = totp::check_code(secret, code, u.totp_last_step) {
+        let res = User::update_many()
+            .col_expr(user::Column::TotpLastStep, Expr::value(step))
+            .filter(user::Column::Id.eq(u.id))
+            .filter(
+                Condition::any()
+                    .add(user::Column::TotpLastStep.is_null())
+                    .add(user::Column::TotpLastStep.lt(step)),
+            )
+            .exec(db)
+            .await?;
+
+        return Ok(res.rows_affected > 0);
+    }
+
+    let res = RecoveryCode::update_many()
+        .col_expr(
+            recovery_code::Column::UsedAt,
+            Expr::value(DateTimeUtc::from(SystemTime::now())),
+        )
+        .filter(recovery_code::Column::UserId.eq(u.id))
+        .filter(recovery_code::Column::CodeHash.eq(hash_token(code.trim())))
+        .filter(recovery_code::Column::UsedAt.is_null())
+        .exec(db)
+        .await?;
+
+    Ok(res.rows_affected > 0)
+}
+
+// 生成新的恢复码，旧的全部作废
+async fn regenerate_recovery_codes(
+    db: &DatabaseConnection,
+    user_id: i32,
+) -> Result<Vec<String>, DbErr> {
+    RecoveryCode::delete_many()
+        .filter(recovery_code::Column::UserId.eq(user_id))
+        .exec(db)
+        .await?;
+
+    let codes = (0..RECOVERY_CODE_COUNT)
+        .map(|_| generate_token(5))
+        .collect::<Vec<_>>();
+
+    RecoveryCode::insert_many(codes.iter().map(|c| recovery_code::ActiveModel {
+        user_id: Set(user_id),
+        code_hash: Set(hash_token(c)),
+        ..Default::default()
+    }))
+    .exec(db)
+    .await?;
+
+    Ok(codes)
+}
+
+#[post("/2fa/enroll")]
+pub async fn enroll(
+    db: &State<DatabaseConnection>,
+    config: &State<AppConfig>,
+    user: SessionUser,
+) -> Response<Json<ResEnroll>> {
+    let db = db as &DatabaseConnection;
+
+    let u = User::find_by_id(user.id).one(db).await?.unwrap();
+
+    if u.totp_enabled_at.is_some() {
+        return Err(ErrorResponse((
+            Status::UnprocessableEntity,
+            "Two-factor authentication is already enabled.".to_string(),
+        )));
+    }
+
+    let secret = totp::generate_secret();
+    let otpauth_uri = match totp::otpauth_uri(&secret, &config.totp_issuer, &u.email) {
+        Some(uri) => uri,
+        None => {
+            return Err(ErrorResponse((
+                Status::InternalServerError,
+                "Cannot generate a TOTP secret.".to_string(),
+            )));
+        }
+    };
+
+    // 确认之前只保存密钥，不启用
+    let mut u: user::ActiveModel = u.into();
+    u.totp_secret = Set(Some(secret.to_owned()));
+    u.update(db).await?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResEnroll {
+            secret,
+            otpauth_uri,
+        }),
+    )))
+}
+
+#[post("/2fa/confirm", data = "<req_code>")]
+pub async fn confirm(
+    db: &State<DatabaseConnection>,
+    user: SessionUser,
+    req_code: Json<ReqCode>,
+) -> Response<Json<ResRecoveryCodes>> {
+    let db = db as &DatabaseConnection;
+
+    let u = User::find_by_id(user.id).one(db).await?.unwrap();
+
+    if u.totp_enabled_at.is_some() {
+        return Err(ErrorResponse((
+            Status::UnprocessableEntity,
+            "Two-factor authentication is already enabled.".to_string(),
+        )));
+    }
+
+    let step = match &u.totp_secret {
+        Some(secret) => totp::check_code(secret, &req_code.code, None),
+        None => None,
+    };
+    let step = match step {
+        Some(step) => step,
+        None => {
+            return Err(ErrorResponse((
+                Status::BadRequest,
+                "Invalid authentication code.".to_string(),
+            )));
+        }
+    };
+
+    let mut u: user::ActiveModel = u.into();
+    u.totp_enabled_at = Set(Some(DateTimeUtc::from(SystemTime::now())));
+    u.totp_last_step = Set(Some(step));
+    u.update(db).await?;
+
+    let recovery_codes = regenerate_recovery_codes(db, user.id).await?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResRecoveryCodes { recovery_codes }),
+    )))
+}
+
+#[post("/2fa/disable", data = "<req_code>")]
+pub async fn disable(
+    db: &State<DatabaseConnection>,
+    user: SessionUser,
+    req_code: Json<ReqCode>,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+
+    let u = User::find_by_id(user.id).one(db).await?.unwrap();
+
+    if u.totp_enabled_at.is_none() || !check_second_factor(db, &u, &req_code.code).await? {
+        return Err(ErrorResponse((
+            Status::BadRequest,
+            "Invalid authentication code.".to_string(),
+        )));
+    }
+
+    let mut u: user::ActiveModel = u.into();
+    u.totp_secret = Set(None);
+    u.totp_enabled_at = Set(None);
+    u.update(db).await?;
+
+    RecoveryCode::delete_many()
+        .filter(recovery_code::Column::UserId.eq(user.id))
+        .exec(db)
+        .await?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        "Two-factor authentication disabled.".to_string(),
+    )))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqTwoFactor {
+    challenge_token: String,
+    code: String,
+}
+
+fn invalid_challenge() -> ErrorResponse {
+    ErrorResponse((
+        Status::Unauthorized,
+        "Invalid or expired challenge token.".to_string(),
+    ))
+}
+
+// 第二步：用临时令牌和动态码（或恢复码）换取访问令牌
+#[post("/2fa/verify", data = "<req_two_factor>")]
+pub async fn verify(
+    db: &State<DatabaseConnection>,
+    config: &State<AppConfig>,
+    keys: &State<KeyStore>,
+    throttle: &State<LoginThrottle>,
+    client: ClientAddr,
+    info: ClientInfo,
+    req_two_factor: Json<ReqTwoFactor>,
+) -> LimitedResponse<Json<ResSignIn>> {
+    let db = db as &DatabaseConnection;
+    let config = config as &AppConfig;
+
+    throttle
+        .check(&client.ip)
+        .map_err(TooManyRequests::retry_after)?;
+
+    let challenge = match totp::decode_challenge(keys, &req_two_factor.challenge_token) {
+        Some(challenge) => challenge,
+        None => return Err(invalid_challenge().into()),
+    };
+    // 已使用过的临时令牌直接拒绝，不再消耗动态码或恢复码
+    let used = RevokedToken::find()
+        .filter(revoked_token::Column::Jti.eq(&challenge.jti))
+        .count(db)
+        .await?;
+    if used > 0 {
+        return Err(invalid_challenge().into());
+    }
+
+    let u = match User::find_by_id(challenge.user_id).one(db).await? {
+        Some(u) if u.totp_enabled_at.is_some() => u,
+        _ => return Err(invalid_challenge().into()),
+    };
+
+    // 与密码登录共用账号锁定，临时令牌有效期内不能无限次尝试动态码
+    ensure_not_locked(db, &info, &u).await?;
+
+    if !check_second_factor(db, &u, &req_two_factor.code).await? {
+        throttle.record_failure(client.ip);
+        info.record(db, Some(u.id), EventType::TwoFactorFailed, None)
+            .await;
+
+        count_failure(db, config, &info, u.id, user::Column::FailedTwoFactor).await?;
+
+        return Err(ErrorResponse((
+            Status::Unauthorized,
+            "Invalid authentication code.".to_string(),
+        ))
+        .into());
+    }
+
+    if u.failed_two_factor > 0 {
+        User::update_many()
+            .col_expr(user::Column::FailedTwoFactor, Expr::value(0))
+            .filter(user::Column::Id.eq(u.id))
+            .exec(db)
+            .await?;
+    }
+
+    // 签发临时令牌之后账号可能已被停用
+    ensure_active(db, config, &info, &u).await?;
+
+    // 临时令牌只能使用一次：jti写入吊销表，唯一索引保证并发请求中只有一个成功
+    let consumed = RevokedToken::insert(revoked_token::ActiveModel {
+        jti: Set(challenge.jti),
+        user_id: Set(u.id),
+        expires_at: Set(DateTimeUtc::from(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(challenge.exp),
+        )),
+        ..Default::default()
+    })
+    .exec(db)
+    .await;
+    if let Err(err) = consumed {
+        return Err(match err.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => invalid_challenge(),
+            _ => err.into(),
+        }
+        .into());
+    }
+
+    let res = issue_tokens(db, config, keys, &u, None).await?;
+    info.record(db, Some(u.id), EventType::SignIn, Some("2fa".to_string()))
+        .await;
+
+    Ok(SuccessResponse((Status::Ok, Json(res))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{auth::Role, testing};
+    use rocket::{
+        http::ContentType,
+        local::asynchronous::Client,
+        serde::json::{json, Value},
+    };
+
+    async fn post(client: &Client, uri: &'static str, body: Value) -> (Status, Value) {
+        let res = client
+            .post(uri)
+            .remote("127.0.0.1:8000".parse().unwrap())
+            .header(ContentType::JSON)
+            .body(body.to_string())
+            .dispatch()
+            .await;
+        let status = res.status();
+
+        (status, res.into_json().await.unwrap_or(Value::Null))
+    }
+
+    #[rocket::async_test]
+    async fn a_challenge_token_can_only_be_used_once() {
+        let app = testing::app(|_| {}).await;
+        let u = testing::create_user(&app.db, "reader@example.com", Role::User).await;
+        let mut active: user::ActiveModel = u.clone().into();
+        active.totp_secret = Set(Some(totp::generate_secret()));
+        active.totp_enabled_at = Set(Some(DateTimeUtc::from(SystemTime::now())));
+        active.update(&app.db).await.unwrap();
+        for code in ["recovery-one", "recovery-two"] {
+            recovery_code::ActiveModel {
+                user_id: Set(u.id),
+                code_hash: Set(hash_token(code)),
+                ..Default::default()
+            }
+            .insert(&app.db)
+            .await
+            .unwrap();
+        }
+
+        let (status, body) = post(
+            &app.client,
+            "/auth/sign-in",
+            json!({"email": "reader@example.com", "password": testing::PASSWORD}),
+        )
+        .await;
+        assert_eq!(status, Status::Ok);
+        let challenge_token = body["challenge_token"].as_str().unwrap();
+
+        let (status, _) = post(
+            &app.client,
+            "/auth/2fa/verify",
+            json!({"challenge_token": challenge_token, "code": "recovery-one"}),
+        )
+        .await;
+        assert_eq!(status, Status::Ok);
+
+        let (status, _) = post(
+            &app.client,
+            "/auth/2fa/verify",
+            json!({"challenge_token": challenge_token, "code": "recovery-two"}),
+        )
+        .await;
+        assert_eq!(status, Status::Unauthorized);
+
+        let unused = RecoveryCode::find()
+            .filter(recovery_code::Column::UsedAt.is_null())
+            .count(&app.db)
+            .await
+            .unwrap();
+        assert_eq!(unused, 1);
+    }
+}
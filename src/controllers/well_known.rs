@@ -0,0 +1,11 @@
+use jsonwebtoken::jwk::JwkSet;
+use rocket::{http::Status, serde::json::Json, State};
+
+use super::{Response, SuccessResponse};
+use crate::auth::keys::KeyStore;
+
+// 公开验证公钥，其他服务可以独立验证本服务签发的令牌
+#[get("/jwks.json")]
+pub fn jwks(keys: &State<KeyStore>) -> Response<Json<JwkSet>> {
+    Ok(SuccessResponse((Status::Ok, Json(keys.jwks().clone()))))
+}
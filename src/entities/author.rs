@@ -27,6 +27,8 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     User,
+    #[sea_orm(has_many = "super::book_contributor::Entity")]
+    BookContributor,
 }
 
 impl Related<super::book::Entity> for Entity {
@@ -41,4 +43,10 @@ impl Related<super::user::Entity> for Entity {
     }
 }
 
+impl Related<super::book_contributor::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BookContributor.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
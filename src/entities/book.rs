@@ -14,6 +14,8 @@ pub struct Model {
     pub cover: String,
     pub created_at: Option<DateTimeUtc>,
     pub updated_at: Option<DateTimeUtc>,
+    pub isbn13: Option<String>,
+    pub isbn10: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -34,6 +36,12 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     User,
+    #[sea_orm(has_many = "super::book_contributor::Entity")]
+    BookContributor,
+    #[sea_orm(has_many = "super::book_genre::Entity")]
+    BookGenre,
+    #[sea_orm(has_many = "super::book_tag::Entity")]
+    BookTag,
 }
 
 impl Related<super::author::Entity> for Entity {
@@ -48,4 +56,22 @@ impl Related<super::user::Entity> for Entity {
     }
 }
 
+impl Related<super::book_contributor::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BookContributor.def()
+    }
+}
+
+impl Related<super::book_genre::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BookGenre.def()
+    }
+}
+
+impl Related<super::book_tag::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BookTag.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -2,6 +2,21 @@
 
 pub mod prelude;
 
+pub mod api_key;
+pub mod auth_event;
 pub mod author;
 pub mod book;
+pub mod book_contributor;
+pub mod book_genre;
+pub mod book_tag;
+pub mod email_change;
+pub mod email_verification_token;
+pub mod genre;
+pub mod invitation;
+pub mod password_reset_token;
+pub mod recovery_code;
+pub mod refresh_token;
+pub mod revoked_token;
+pub mod tag;
 pub mod user;
+pub mod user_identity;
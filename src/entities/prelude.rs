@@ -1,5 +1,20 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
+pub use super::api_key::Entity as ApiKey;
+pub use super::auth_event::Entity as AuthEvent;
 pub use super::author::Entity as Author;
 pub use super::book::Entity as Book;
+pub use super::book_contributor::Entity as BookContributor;
+pub use super::book_genre::Entity as BookGenre;
+pub use super::book_tag::Entity as BookTag;
+pub use super::email_change::Entity as EmailChange;
+pub use super::email_verification_token::Entity as EmailVerificationToken;
+pub use super::genre::Entity as Genre;
+pub use super::invitation::Entity as Invitation;
+pub use super::password_reset_token::Entity as PasswordResetToken;
+pub use super::recovery_code::Entity as RecoveryCode;
+pub use super::refresh_token::Entity as RefreshToken;
+pub use super::revoked_token::Entity as RevokedToken;
+pub use super::tag::Entity as Tag;
 pub use super::user::Entity as User;
+pub use super::user_identity::Entity as UserIdentity;
@@ -14,6 +14,16 @@ pub struct Model {
     pub lastname: Option<String>,
     pub created_at: Option<DateTimeUtc>,
     pub updated_at: Option<DateTimeUtc>,
+    pub role: String,
+    pub verified_at: Option<DateTimeUtc>,
+    pub failed_logins: i32,
+    pub locked_until: Option<DateTimeUtc>,
+    pub totp_secret: Option<String>,
+    pub totp_enabled_at: Option<DateTimeUtc>,
+    pub disabled_at: Option<DateTimeUtc>,
+    pub session_version: i32,
+    pub totp_last_step: Option<i64>,
+    pub failed_two_factor: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -22,6 +32,28 @@ pub enum Relation {
     Author,
     #[sea_orm(has_many = "super::book::Entity")]
     Book,
+    #[sea_orm(has_many = "super::refresh_token::Entity")]
+    RefreshToken,
+    #[sea_orm(has_many = "super::revoked_token::Entity")]
+    RevokedToken,
+    #[sea_orm(has_many = "super::password_reset_token::Entity")]
+    PasswordResetToken,
+    #[sea_orm(has_many = "super::email_verification_token::Entity")]
+    EmailVerificationToken,
+    #[sea_orm(has_many = "super::recovery_code::Entity")]
+    RecoveryCode,
+    #[sea_orm(has_many = "super::api_key::Entity")]
+    ApiKey,
+    #[sea_orm(has_many = "super::email_change::Entity")]
+    EmailChange,
+    #[sea_orm(has_many = "super::user_identity::Entity")]
+    UserIdentity,
+    #[sea_orm(has_many = "super::auth_event::Entity")]
+    AuthEvent,
+    #[sea_orm(has_many = "super::tag::Entity")]
+    Tag,
+    #[sea_orm(has_many = "super::book_tag::Entity")]
+    BookTag,
 }
 
 impl Related<super::author::Entity> for Entity {
@@ -36,4 +68,70 @@ impl Related<super::book::Entity> for Entity {
     }
 }
 
+impl Related<super::refresh_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RefreshToken.def()
+    }
+}
+
+impl Related<super::revoked_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RevokedToken.def()
+    }
+}
+
+impl Related<super::password_reset_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PasswordResetToken.def()
+    }
+}
+
+impl Related<super::email_verification_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::EmailVerificationToken.def()
+    }
+}
+
+impl Related<super::recovery_code::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RecoveryCode.def()
+    }
+}
+
+impl Related<super::api_key::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApiKey.def()
+    }
+}
+
+impl Related<super::email_change::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::EmailChange.def()
+    }
+}
+
+impl Related<super::user_identity::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserIdentity.def()
+    }
+}
+
+impl Related<super::auth_event::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AuthEvent.def()
+    }
+}
+
+impl Related<super::tag::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Tag.def()
+    }
+}
+
+impl Related<super::book_tag::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BookTag.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
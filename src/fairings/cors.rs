@@ -1,8 +1,8 @@
 use rocket::{fairing::{Fairing, Info, Kind}, http:: Header, Request, Response};
-pub struct CORS;
+pub struct Cors;
 
 #[rocket::async_trait]
-impl Fairing for CORS {
+impl Fairing for Cors {
     fn info(&self) -> Info {
         Info {
             name: "Add CORS headers",
@@ -13,8 +13,9 @@ impl Fairing for CORS {
     async fn on_response<'r>(&self, _request: &'r Request<'_>, response: &mut Response<'r>) {
         response.set_header(Header::new("Access-Control-Allow-Origin", "*"));       // 允许所有的源访问
         response.set_header(Header::new("Access-Control-Allow-Methods", "GET, POST, PUT, PATCH, DELETE, OPTIONS"));     // 允许所有的方法访问
-        response.set_header(Header::new("Access-Crontol-Allow-Headers", "*"));
+        response.set_header(Header::new("Access-Control-Allow-Headers", "Authorization, Token, Content-Type"));  // 带凭据时浏览器不把*当作通配符
         response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+        response.set_header(Header::new("Access-Control-Expose-Headers", "WWW-Authenticate, Retry-After, Link"));
     }
 }
 
@@ -0,0 +1,198 @@
+use std::fmt;
+
+// 国际标准书号，内部统一保存为ISBN-13
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Isbn {
+    digits: [u8; 13],
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum IsbnError {
+    InvalidFormat,
+    InvalidChecksum,
+}
+
+impl fmt::Display for IsbnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IsbnError::InvalidFormat => write!(f, "An ISBN must have 10 or 13 digits."),
+            IsbnError::InvalidChecksum => write!(f, "The ISBN check digit is incorrect."),
+        }
+    }
+}
+
+// ISBN-10校验位：各位依次乘以10到2求和，补足为11的倍数，10记为X
+fn isbn10_check(digits: &[u8]) -> u8 {
+    let sum: u32 = digits
+        .iter()
+        .take(9)
+        .enumerate()
+        .map(|(i, d)| (10 - i as u32) * *d as u32)
+        .sum();
+
+    ((11 - sum % 11) % 11) as u8
+}
+
+// ISBN-13校验位：各位交替乘以1和3求和，补足为10的倍数
+fn isbn13_check(digits: &[u8]) -> u8 {
+    let sum: u32 = digits
+        .iter()
+        .take(12)
+        .enumerate()
+        .map(|(i, d)| if i % 2 == 0 { *d as u32 } else { 3 * *d as u32 })
+        .sum();
+
+    ((10 - sum % 10) % 10) as u8
+}
+
+impl Isbn {
+    // 接受带或不带连字符、空格和"ISBN"前缀的写法
+    pub fn parse(input: &str) -> Result<Self, IsbnError> {
+        let input = input.trim();
+        let input = input
+            .strip_prefix("ISBN-13:")
+            .or(input.strip_prefix("ISBN-10:"))
+            .or(input.strip_prefix("ISBN"))
+            .unwrap_or(input)
+            .trim_start_matches(':');
+
+        let mut digits = vec![];
+        for (i, c) in input
+            .chars()
+            .filter(|c| *c != '-' && !c.is_whitespace())
+            .enumerate()
+        {
+            match c {
+                '0'..='9' => digits.push(c as u8 - b'0'),
+                // X只能出现在ISBN-10的最后一位
+                'X' | 'x' if i == 9 => digits.push(10),
+                _ => return Err(IsbnError::InvalidFormat),
+            }
+        }
+
+        match digits.len() {
+            10 => {
+                if isbn10_check(&digits) != digits[9] {
+                    return Err(IsbnError::InvalidChecksum);
+                }
+
+                let mut isbn13 = [0; 13];
+                isbn13[..3].copy_from_slice(&[9, 7, 8]);
+                isbn13[3..12].copy_from_slice(&digits[..9]);
+                isbn13[12] = isbn13_check(&isbn13);
+
+                Ok(Self { digits: isbn13 })
+            }
+            13 if !digits.contains(&10) => {
+                if isbn13_check(&digits) != digits[12] {
+                    return Err(IsbnError::InvalidChecksum);
+                }
+                if digits[..3] != [9, 7, 8] && digits[..3] != [9, 7, 9] {
+                    return Err(IsbnError::InvalidFormat);
+                }
+
+                let mut isbn13 = [0; 13];
+                isbn13.copy_from_slice(&digits);
+
+                Ok(Self { digits: isbn13 })
+            }
+            _ => Err(IsbnError::InvalidFormat),
+        }
+    }
+
+    pub fn to_isbn13(&self) -> String {
+        self.digits.iter().map(|d| (b'0' + d) as char).collect()
+    }
+
+    // 只有978开头的ISBN-13有对应的ISBN-10
+    pub fn to_isbn10(&self) -> Option<String> {
+        if self.digits[..3] != [9, 7, 8] {
+            return None;
+        }
+
+        let body = &self.digits[3..12];
+        let check = match isbn10_check(body) {
+            10 => 'X',
+            d => (b'0' + d) as char,
+        };
+
+        Some(
+            body.iter()
+                .map(|d| (b'0' + d) as char)
+                .chain(std::iter::once(check))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_isbn10_with_x_check_digit() {
+        let isbn = Isbn::parse("0-8044-2957-X").unwrap();
+        assert_eq!(isbn.to_isbn13(), "9780804429573");
+        assert_eq!(isbn.to_isbn10().as_deref(), Some("080442957X"));
+
+        assert_eq!(Isbn::parse("080442957x").unwrap(), isbn);
+    }
+
+    #[test]
+    fn rejects_x_outside_the_last_isbn10_digit() {
+        assert_eq!(Isbn::parse("08044X2957"), Err(IsbnError::InvalidFormat));
+        assert_eq!(Isbn::parse("978080442957X"), Err(IsbnError::InvalidFormat));
+    }
+
+    #[test]
+    fn parses_isbn13_with_979_prefix() {
+        let isbn = Isbn::parse("979-10-90636-07-1").unwrap();
+        assert_eq!(isbn.to_isbn13(), "9791090636071");
+        assert_eq!(isbn.to_isbn10(), None);
+    }
+
+    #[test]
+    fn rejects_other_isbn13_prefixes() {
+        assert_eq!(Isbn::parse("9771234567003"), Err(IsbnError::InvalidFormat));
+    }
+
+    #[test]
+    fn strips_hyphens_spaces_and_prefixes() {
+        let expected = "9780306406157";
+        for input in [
+            "978-0-306-40615-7",
+            "978 0 306 40615 7",
+            " ISBN 978-0-306-40615-7 ",
+            "ISBN-13: 978-0-306-40615-7",
+            "ISBN-10: 0-306-40615-2",
+            "ISBN:0306406152",
+        ] {
+            assert_eq!(
+                Isbn::parse(input).unwrap().to_isbn13(),
+                expected,
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_bad_checksums() {
+        assert_eq!(
+            Isbn::parse("0-306-40615-3"),
+            Err(IsbnError::InvalidChecksum)
+        );
+        assert_eq!(
+            Isbn::parse("978-0-306-40615-8"),
+            Err(IsbnError::InvalidChecksum)
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_lengths_and_characters() {
+        assert_eq!(Isbn::parse(""), Err(IsbnError::InvalidFormat));
+        assert_eq!(Isbn::parse("030640615"), Err(IsbnError::InvalidFormat));
+        assert_eq!(Isbn::parse("03064061521"), Err(IsbnError::InvalidFormat));
+        assert_eq!(Isbn::parse("0-306-4O615-2"), Err(IsbnError::InvalidFormat));
+    }
+}
@@ -0,0 +1,25 @@
+use crate::AppConfig;
+
+pub mod outbox;
+pub mod smtp;
+
+#[derive(Debug)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// 邮件发送接口，可切换SMTP或本地发件箱
+#[rocket::async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: Mail) -> Result<(), String>;
+}
+
+// 根据配置选择邮件发送方式，默认写入本地发件箱
+pub fn from_config(config: &AppConfig) -> Box<dyn Mailer> {
+    match config.mailer.as_str() {
+        "smtp" => Box::new(smtp::SmtpMailer::new(config)),
+        _ => Box::new(outbox::OutboxMailer::new(&config.outbox_dir)),
+    }
+}
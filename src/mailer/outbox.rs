@@ -0,0 +1,60 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rocket::{
+    serde::{json, Serialize},
+    tokio::fs,
+};
+
+use super::{Mail, Mailer};
+use crate::auth::generate_token;
+
+// 把邮件以JSON文件写入本地目录，便于开发和测试时查看
+pub struct OutboxMailer {
+    dir: PathBuf,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct OutboxMail<'a> {
+    to: &'a str,
+    subject: &'a str,
+    body: &'a str,
+}
+
+impl OutboxMailer {
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Mailer for OutboxMailer {
+    async fn send(&self, mail: Mail) -> Result<(), String> {
+        fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        // 文件名按时间排序，后缀防止同一时刻重名
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let path = self
+            .dir
+            .join(format!("{}-{}.json", millis, generate_token(4)));
+
+        let content = json::to_pretty_string(&OutboxMail {
+            to: &mail.to,
+            subject: &mail.subject,
+            body: &mail.body,
+        })
+        .map_err(|e| e.to_string())?;
+
+        fs::write(path, content).await.map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,49 @@
+use lettre::{
+    message::header::ContentType, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+
+use super::{Mail, Mailer};
+use crate::AppConfig;
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: String,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &AppConfig) -> Self {
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)
+            .expect("[-] SMTP配置错误")
+            .port(config.smtp_port)
+            .credentials(Credentials::new(
+                config.smtp_username.to_owned(),
+                config.smtp_password.to_owned(),
+            ))
+            .build();
+
+        Self {
+            transport,
+            from: config.mail_from.to_owned(),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, mail: Mail) -> Result<(), String> {
+        let message = Message::builder()
+            .from(self.from.parse().map_err(|e| format!("{}", e))?)
+            .to(mail.to.parse().map_err(|e| format!("{}", e))?)
+            .subject(mail.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(mail.body)
+            .map_err(|e| e.to_string())?;
+
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
@@ -1,18 +1,25 @@
 use controllers::SuccessResponse;
 use fairings::cors::options;
 use migrator::Migrator;
-use rocket::http::Status;
+use rocket::{http::Status, Build, Rocket};
+use sea_orm::{DatabaseConnection, EntityTrait};
 use sea_orm_migration::MigratorTrait;
 
 #[macro_use]
 extern crate rocket;
 
 mod auth;
+mod catchers;
 mod controllers;
 mod db;
 mod entities;
 mod fairings;
+mod isbn;
+mod mailer;
 mod migrator;
+mod search;
+#[cfg(test)]
+mod testing;
 
 pub struct AppConfig {
     db_host: String,
@@ -20,7 +27,88 @@ pub struct AppConfig {
     db_username: String,
     db_password: String,
     db_database: String,
-    jwt_sercert: String,
+    jwt_sercert: Option<String>,
+    jwt_keys_dir: Option<String>,    // RS256/EdDSA密钥目录
+    jwt_signing_kid: Option<String>, // 当前用于签名的密钥ID
+    jwt_accept_legacy_hs256: bool,   // 配置密钥目录后是否仍接受没有kid的HS256旧令牌
+    access_token_ttl: u64,  // 访问令牌有效期（秒）
+    refresh_token_ttl: u64, // 刷新令牌有效期（秒）
+    reset_token_ttl: u64,   // 重置密码令牌有效期（秒）
+    app_url: String,        // 邮件中链接的前端地址
+    mailer: String,         // 邮件发送方式：smtp或outbox
+    outbox_dir: String,
+    smtp_host: String,
+    smtp_port: u16,
+    smtp_username: String,
+    smtp_password: String,
+    mail_from: String,
+    verify_token_ttl: u64,     // 邮箱验证令牌有效期（秒）
+    unverified_policy: UnverifiedPolicy, // 未验证邮箱的限制
+    login_max_failures: i32,   // 账号连续登录失败上限
+    login_lockout_secs: u64,   // 账号锁定时长（秒）
+    ip_max_failures: usize,    // 单个IP在窗口内的登录失败上限
+    ip_window_secs: u64,       // IP限流窗口（秒）
+    audit_max_token_errors: usize, // 单个IP在窗口内记录的令牌错误审计日志上限
+    reset_max_requests: usize, // 同一邮箱在窗口内可申请重置密码的次数
+    reset_window_secs: u64,    // 重置密码限流窗口（秒）
+    trusted_proxy: bool,       // 是否信任X-Forwarded-For等代理请求头
+    totp_issuer: String,       // 身份验证器App中显示的名称
+    challenge_token_ttl: u64,  // 两步验证临时令牌有效期（秒）
+    reassign_owner_id: Option<i32>, // 注销账号时接收其作者和书籍的用户
+    password_min_length: usize,
+    password_max_length: usize,
+    breached_passwords_file: Option<String>, // 泄露密码列表，每行一个
+    oidc_issuer: Option<String>,             // OIDC身份提供方，未设置时不启用
+    oidc_client_id: Option<String>,
+    oidc_client_secret: Option<String>,
+    oidc_redirect_url: Option<String>, // 默认为{app_url}/auth/oidc/callback
+    oidc_scopes: String,
+    oidc_auto_provision: bool, // 首次登录时自动创建账号
+    impersonation_ttl: u64,    // 管理员代为操作令牌的有效期（秒）
+    registration_mode: RegistrationMode, // 注册方式
+    search_index_dir: String,  // 全文搜索索引目录
+}
+
+// 未验证邮箱的用户受到的限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnverifiedPolicy {
+    Allow,
+    BlockWrites, // 禁止写操作
+    BlockSignIn, // 禁止登录
+}
+
+impl std::str::FromStr for UnverifiedPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(UnverifiedPolicy::Allow),
+            "block-writes" => Ok(UnverifiedPolicy::BlockWrites),
+            "block-sign-in" => Ok(UnverifiedPolicy::BlockSignIn),
+            _ => Err(format!("Unknown unverified policy: {}", s)),
+        }
+    }
+}
+
+// 注册方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistrationMode {
+    Open,
+    InviteOnly, // 需要邀请码
+    Closed,
+}
+
+impl std::str::FromStr for RegistrationMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(RegistrationMode::Open),
+            "invite-only" => Ok(RegistrationMode::InviteOnly),
+            "closed" => Ok(RegistrationMode::Closed),
+            _ => Err(format!("Unknown registration mode: {}", s)),
+        }
+    }
 }
 
 impl AppConfig {
@@ -32,8 +120,129 @@ impl AppConfig {
             db_password: std::env::var("BOOKSTORE_DB_PASSWORD")
                 .unwrap_or("ZhangYing.730298".to_string()),
             db_database: std::env::var("BOOKSTORE_DB_DATABASE").unwrap_or("bookstore".to_string()),
-            jwt_sercert: std::env::var("BOOKSTORE_JWT_SECRET")
-                .expect("Please set the BOOKSTORE_JWT_SECRET env variable."),
+            jwt_sercert: std::env::var("BOOKSTORE_JWT_SECRET").ok(),
+            jwt_keys_dir: std::env::var("BOOKSTORE_JWT_KEYS_DIR").ok(),
+            jwt_signing_kid: std::env::var("BOOKSTORE_JWT_SIGNING_KID").ok(),
+            jwt_accept_legacy_hs256: std::env::var("BOOKSTORE_JWT_ACCEPT_LEGACY_HS256")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(false),
+            access_token_ttl: std::env::var("BOOKSTORE_ACCESS_TOKEN_TTL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(15 * 60),
+            refresh_token_ttl: std::env::var("BOOKSTORE_REFRESH_TOKEN_TTL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30 * 24 * 60 * 60),
+            reset_token_ttl: std::env::var("BOOKSTORE_RESET_TOKEN_TTL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60 * 60),
+            app_url: std::env::var("BOOKSTORE_APP_URL")
+                .unwrap_or("http://localhost:8000".to_string()),
+            mailer: std::env::var("BOOKSTORE_MAILER").unwrap_or("outbox".to_string()),
+            outbox_dir: std::env::var("BOOKSTORE_OUTBOX_DIR").unwrap_or("outbox".to_string()),
+            smtp_host: std::env::var("BOOKSTORE_SMTP_HOST").unwrap_or("localhost".to_string()),
+            smtp_port: std::env::var("BOOKSTORE_SMTP_PORT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(587),
+            smtp_username: std::env::var("BOOKSTORE_SMTP_USERNAME").unwrap_or_default(),
+            smtp_password: std::env::var("BOOKSTORE_SMTP_PASSWORD").unwrap_or_default(),
+            mail_from: std::env::var("BOOKSTORE_MAIL_FROM")
+                .unwrap_or("BookStore <no-reply@localhost>".to_string()),
+            verify_token_ttl: std::env::var("BOOKSTORE_VERIFY_TOKEN_TTL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(24 * 60 * 60),
+            unverified_policy: std::env::var("BOOKSTORE_UNVERIFIED_POLICY")
+                .unwrap_or("allow".to_string())
+                .parse()
+                .expect("[-] BOOKSTORE_UNVERIFIED_POLICY只能为allow、block-writes或block-sign-in"),
+            login_max_failures: std::env::var("BOOKSTORE_LOGIN_MAX_FAILURES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+            login_lockout_secs: std::env::var("BOOKSTORE_LOGIN_LOCKOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(15 * 60),
+            ip_max_failures: std::env::var("BOOKSTORE_IP_MAX_FAILURES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(20),
+            ip_window_secs: std::env::var("BOOKSTORE_IP_WINDOW_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5 * 60),
+            audit_max_token_errors: std::env::var("BOOKSTORE_AUDIT_MAX_TOKEN_ERRORS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(20),
+            reset_max_requests: std::env::var("BOOKSTORE_RESET_MAX_REQUESTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3),
+            reset_window_secs: std::env::var("BOOKSTORE_RESET_WINDOW_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60 * 60),
+            trusted_proxy: std::env::var("BOOKSTORE_TRUSTED_PROXY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(false),
+            totp_issuer: std::env::var("BOOKSTORE_TOTP_ISSUER").unwrap_or("BookStore".to_string()),
+            challenge_token_ttl: std::env::var("BOOKSTORE_CHALLENGE_TOKEN_TTL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5 * 60),
+            reassign_owner_id: std::env::var("BOOKSTORE_REASSIGN_OWNER_ID")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+            password_min_length: std::env::var("BOOKSTORE_PASSWORD_MIN_LENGTH")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(8),
+            password_max_length: std::env::var("BOOKSTORE_PASSWORD_MAX_LENGTH")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(128),
+            breached_passwords_file: std::env::var("BOOKSTORE_BREACHED_PASSWORDS_FILE").ok(),
+            oidc_issuer: std::env::var("BOOKSTORE_OIDC_ISSUER").ok(),
+            oidc_client_id: std::env::var("BOOKSTORE_OIDC_CLIENT_ID").ok(),
+            oidc_client_secret: std::env::var("BOOKSTORE_OIDC_CLIENT_SECRET").ok(),
+            oidc_redirect_url: std::env::var("BOOKSTORE_OIDC_REDIRECT_URL").ok(),
+            oidc_scopes: std::env::var("BOOKSTORE_OIDC_SCOPES")
+                .unwrap_or("openid email profile".to_string()),
+            oidc_auto_provision: std::env::var("BOOKSTORE_OIDC_AUTO_PROVISION")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(true),
+            impersonation_ttl: std::env::var("BOOKSTORE_IMPERSONATION_TTL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(15 * 60),
+            registration_mode: std::env::var("BOOKSTORE_REGISTRATION_MODE")
+                .unwrap_or("open".to_string())
+                .parse()
+                .expect("[-] BOOKSTORE_REGISTRATION_MODE只能为open、invite-only或closed"),
+            search_index_dir: std::env::var("BOOKSTORE_SEARCH_INDEX_DIR")
+                .unwrap_or("search_index".to_string()),
+        }
+    }
+
+    // 注销账号时接收内容的用户必须存在且未被停用，配置错误时启动失败
+    async fn check_reassign_owner(&self, db: &DatabaseConnection) {
+        let Some(id) = self.reassign_owner_id else {
+            return;
+        };
+
+        match entities::prelude::User::find_by_id(id).one(db).await {
+            Ok(Some(u)) if u.disabled_at.is_none() => {}
+            Ok(Some(_)) => panic!("[-] BOOKSTORE_REASSIGN_OWNER_ID对应的用户已被停用"),
+            Ok(None) => panic!("[-] BOOKSTORE_REASSIGN_OWNER_ID对应的用户不存在"),
+            Err(err) => panic!("[-] 检查BOOKSTORE_REASSIGN_OWNER_ID失败{}", err),
         }
     }
 }
@@ -50,29 +259,139 @@ async fn rocket() -> _ {
     // env::set_var("ROCKET_PORT", "80");
 
     let config = AppConfig::new();
+    // 创建Rocket实例时会初始化日志，后面的启动步骤才能输出日志
+    let app = rocket::build();
 
     let db = match db::connect(&config).await {
         Ok(db) => db,
         Err(err) => panic!("[-]数据库连接失败{}", err),
     };
 
-    match Migrator::up(&db, None).await {
-        Err(err) => panic!("[-] 数据库迁移失败{}", err),
-        Ok(_) => (),
-    };
+    if let Err(err) = Migrator::up(&db, None).await {
+        panic!("[-] 数据库迁移失败{}", err);
+    }
+
+    // cargo run -- promote-admin <email>：把已注册的账号设为管理员后退出，用于创建第一个管理员
+    if std::env::args().nth(1).as_deref() == Some("promote-admin") {
+        let email = std::env::args()
+            .nth(2)
+            .expect("[-] 用法：promote-admin <email>");
+        match auth::promote_admin(&db, &email).await {
+            Ok(true) => info!("{}已设为管理员", email),
+            Ok(false) => panic!("[-] 账号{}不存在", email),
+            Err(err) => panic!("[-] 设置管理员失败{}", err),
+        }
+        std::process::exit(0);
+    }
+
+    config.check_reassign_owner(&db).await;
+
+    let search = search::SearchIndex::open(&config);
+
+    // cargo run -- rebuild-search-index：重建搜索索引后退出。
+    // 索引目录同时只能被一个进程写入，服务运行时请改用POST /admin/search/rebuild
+    let rebuild = std::env::args().nth(1).as_deref() == Some("rebuild-search-index");
+    if rebuild || search.is_empty() {
+        match search.rebuild(&db).await {
+            Ok(count) => info!("搜索索引已重建，共{}条", count),
+            Err(err) => panic!("[-] 重建搜索索引失败{}", err),
+        }
+        if rebuild {
+            std::process::exit(0);
+        }
+    }
 
-    rocket::build()
-        .attach(fairings::cors::CORS)
+    build(app, config, db, search)
+}
+
+// 挂载共享状态和路由，测试用同一个函数构建实例
+fn build(
+    app: Rocket<Build>,
+    config: AppConfig,
+    db: DatabaseConnection,
+    search: search::SearchIndex,
+) -> Rocket<Build> {
+    let keys = auth::keys::KeyStore::from_config(&config);
+    let mailer = mailer::from_config(&config);
+    let throttle =
+        auth::throttle::LoginThrottle::new(config.ip_max_failures, config.ip_window_secs);
+    let audit_throttle =
+        auth::throttle::AuditThrottle::new(config.audit_max_token_errors, config.ip_window_secs);
+    let reset_throttle =
+        auth::throttle::ResetThrottle::new(config.reset_max_requests, config.reset_window_secs);
+    let password_policy = auth::password::PasswordPolicy::from_config(&config);
+    let oidc = auth::oidc::OidcProvider::from_config(&config);
+
+    app.attach(fairings::cors::Cors)
+        .register("/", catchers![catchers::unauthorized, catchers::forbidden])
         .manage(db)
+        .manage(keys)
+        .manage(mailer)
+        .manage(throttle)
+        .manage(audit_throttle)
+        .manage(reset_throttle)
+        .manage(password_policy)
+        .manage(oidc)
+        .manage(search)
         .manage(config)
         .mount("/", routes![options])
         .mount("/", routes![index])
+        .mount("/.well-known", routes![controllers::well_known::jwks])
         .mount(
             "/auth",
             routes![
                 controllers::auth::sigin_in,
                 controllers::auth::sigin_up,
                 controllers::auth::me,
+                controllers::auth::refresh,
+                controllers::auth::sign_out,
+                controllers::auth::sign_out_all,
+                controllers::auth::forgot_password,
+                controllers::auth::reset_password,
+                controllers::auth::verify_email,
+                controllers::auth::resend_verification,
+                controllers::two_factor::enroll,
+                controllers::two_factor::confirm,
+                controllers::two_factor::disable,
+                controllers::two_factor::verify,
+                controllers::oidc::login,
+                controllers::oidc::callback,
+                controllers::profile::update_me,
+                controllers::profile::change_password,
+                controllers::profile::change_email,
+                controllers::profile::confirm_email_change,
+                controllers::profile::export,
+                controllers::profile::delete_me,
+            ],
+        )
+        .mount(
+            "/admin",
+            routes![
+                controllers::admin::users,
+                controllers::admin::show_user,
+                controllers::admin::update_role,
+                controllers::admin::disable_user,
+                controllers::admin::enable_user,
+                controllers::admin::force_password_reset,
+                controllers::admin::auth_events,
+                controllers::admin::impersonate,
+                controllers::admin::rebuild_search_index,
+            ],
+        )
+        .mount(
+            "/admin/invitations",
+            routes![
+                controllers::invitations::index,
+                controllers::invitations::create,
+                controllers::invitations::delete,
+            ],
+        )
+        .mount(
+            "/api-keys",
+            routes![
+                controllers::api_keys::index,
+                controllers::api_keys::create,
+                controllers::api_keys::revoke,
             ],
         )
         .mount(
@@ -92,8 +411,31 @@ async fn rocket() -> _ {
                 controllers::books::index,
                 controllers::books::create,
                 controllers::books::show,
+                controllers::books::show_by_isbn,
                 controllers::books::update,
                 controllers::books::delete,
+                controllers::tags::tag_book,
+                controllers::tags::untag_book,
+            ],
+        )
+        .mount(
+            "/genres",
+            routes![
+                controllers::genres::index,
+                controllers::genres::show,
+                controllers::genres::create,
+                controllers::genres::update,
+                controllers::genres::delete,
+            ],
+        )
+        .mount(
+            "/tags",
+            routes![
+                controllers::tags::index,
+                controllers::tags::create,
+                controllers::tags::update,
+                controllers::tags::delete,
             ],
         )
+        .mount("/search", routes![controllers::search::search])
 }
@@ -1,5 +1,5 @@
 use sea_orm_migration::prelude::*;
-use super::m20220101_000001_create_user_table::{self, User};
+use super::m20220101_000001_create_user_table::User;
 
 #[derive(DeriveMigrationName)]
 pub struct Migration;
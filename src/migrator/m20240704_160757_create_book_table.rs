@@ -1,8 +1,6 @@
 use sea_orm_migration::prelude::*;
 
-use super::m20220101_000001_create_user_table;
 use super::m20220101_000001_create_user_table::User;
-use super::m20240704_155437_create_author_table;
 use super::m20240704_155437_create_author_table::Author;
 
 #[derive(DeriveMigrationName)]
@@ -65,7 +63,7 @@ impl MigrationTrait for Migration {
 }
 
 #[derive(Iden)]
-enum Book {
+pub enum Book {
     Table,
     Id,
     UserId,
@@ -0,0 +1,95 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20220101_000001_create_user_table::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RevokedToken::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RevokedToken::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(RevokedToken::Jti)
+                            .string_len(32)
+                            .unique_key()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(RevokedToken::UserId).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-revoked_token-user_id")
+                            .from(RevokedToken::Table, RevokedToken::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .col(
+                        ColumnDef::new(RevokedToken::ExpiresAt)
+                            .timestamp()
+                            .not_null(),
+                    ) // 过期后即可清理
+                    .col(
+                        ColumnDef::new(RevokedToken::CreatedAt)
+                            .timestamp()
+                            .extra("DEFAULT CURRENT_TIMESTAMP".to_owned()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // 退出所有会话时版本号加一，签发时版本不同的令牌全部失效
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(
+                        ColumnDef::new(UserSessions::SessionVersion)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(UserSessions::SessionVersion)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(RevokedToken::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum RevokedToken {
+    Table,
+    Id,
+    Jti,
+    UserId,
+    ExpiresAt,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum UserSessions {
+    SessionVersion,
+}
@@ -0,0 +1,110 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20220101_000001_create_user_table::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(ColumnDef::new(UserVerified::VerifiedAt).timestamp().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // 已有用户视为已验证，避免升级后无法登录
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(User::Table)
+                    .value(UserVerified::VerifiedAt, Expr::current_timestamp())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(EmailVerificationToken::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(EmailVerificationToken::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(EmailVerificationToken::UserId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-email_verification_token-user_id")
+                            .from(
+                                EmailVerificationToken::Table,
+                                EmailVerificationToken::UserId,
+                            )
+                            .to(User::Table, User::Id),
+                    )
+                    .col(
+                        ColumnDef::new(EmailVerificationToken::TokenHash)
+                            .string_len(64)
+                            .unique_key()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(EmailVerificationToken::ExpiresAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(EmailVerificationToken::CreatedAt)
+                            .timestamp()
+                            .extra("DEFAULT CURRENT_TIMESTAMP".to_owned()),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(EmailVerificationToken::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(UserVerified::VerifiedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum EmailVerificationToken {
+    Table,
+    Id,
+    UserId,
+    TokenHash,
+    ExpiresAt,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum UserVerified {
+    VerifiedAt,
+}
@@ -0,0 +1,106 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20220101_000001_create_user_table::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(ColumnDef::new(UserTwoFactor::TotpSecret).string().null())
+                    .add_column(
+                        ColumnDef::new(UserTwoFactor::TotpEnabledAt)
+                            .timestamp()
+                            .null(), // 为空表示未启用或尚未确认
+                    )
+                    .add_column(
+                        ColumnDef::new(UserTwoFactor::TotpLastStep)
+                            .big_integer()
+                            .null(), // 最后一次通过的动态码时间步，防止重放
+                    )
+                    .add_column(
+                        ColumnDef::new(UserTwoFactor::FailedTwoFactor)
+                            .integer()
+                            .not_null()
+                            .default(0), // 连续两步验证失败次数
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(RecoveryCode::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RecoveryCode::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(RecoveryCode::UserId).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-recovery_code-user_id")
+                            .from(RecoveryCode::Table, RecoveryCode::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .col(
+                        ColumnDef::new(RecoveryCode::CodeHash)
+                            .string_len(64)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(RecoveryCode::UsedAt).timestamp().null())
+                    .col(
+                        ColumnDef::new(RecoveryCode::CreatedAt)
+                            .timestamp()
+                            .extra("DEFAULT CURRENT_TIMESTAMP".to_owned()),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RecoveryCode::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(UserTwoFactor::TotpSecret)
+                    .drop_column(UserTwoFactor::TotpEnabledAt)
+                    .drop_column(UserTwoFactor::TotpLastStep)
+                    .drop_column(UserTwoFactor::FailedTwoFactor)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum UserTwoFactor {
+    TotpSecret,
+    TotpEnabledAt,
+    TotpLastStep,
+    FailedTwoFactor,
+}
+
+#[derive(Iden)]
+enum RecoveryCode {
+    Table,
+    Id,
+    UserId,
+    CodeHash,
+    UsedAt,
+    CreatedAt,
+}
@@ -0,0 +1,68 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20220101_000001_create_user_table::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(EmailChange::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(EmailChange::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(EmailChange::UserId).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-email_change-user_id")
+                            .from(EmailChange::Table, EmailChange::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .col(ColumnDef::new(EmailChange::NewEmail).string().not_null())
+                    .col(
+                        ColumnDef::new(EmailChange::TokenHash)
+                            .string_len(64)
+                            .unique_key()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(EmailChange::ExpiresAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(EmailChange::CreatedAt)
+                            .timestamp()
+                            .extra("DEFAULT CURRENT_TIMESTAMP".to_owned()),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(EmailChange::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum EmailChange {
+    Table,
+    Id,
+    UserId,
+    NewEmail,
+    TokenHash,
+    ExpiresAt,
+    CreatedAt,
+}
@@ -0,0 +1,77 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20220101_000001_create_user_table::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuthEvent::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AuthEvent::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(AuthEvent::UserId).integer().null()) // 未知用户（如邮箱不存在）时为空
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-auth_event-user_id")
+                            .from(AuthEvent::Table, AuthEvent::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .col(
+                        ColumnDef::new(AuthEvent::EventType)
+                            .string_len(32)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(AuthEvent::Ip).string_len(45).null())
+                    .col(ColumnDef::new(AuthEvent::UserAgent).string().null())
+                    .col(ColumnDef::new(AuthEvent::Detail).string().null()) // 登录邮箱、失败原因等
+                    .col(
+                        ColumnDef::new(AuthEvent::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .extra("DEFAULT CURRENT_TIMESTAMP".to_owned()),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx-auth_event-user_id-created_at")
+                            .col(AuthEvent::UserId)
+                            .col(AuthEvent::CreatedAt),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx-auth_event-created_at")
+                            .col(AuthEvent::CreatedAt),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AuthEvent::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum AuthEvent {
+    Table,
+    Id,
+    UserId,
+    EventType,
+    Ip,
+    UserAgent,
+    Detail,
+    CreatedAt,
+}
@@ -0,0 +1,79 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20220101_000001_create_user_table::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Invitation::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Invitation::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Invitation::CodeHash)
+                            .string_len(64)
+                            .unique_key()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(Invitation::Role)
+                            .string_len(16)
+                            .not_null()
+                            .default("user"), // 注册后分配的角色
+                    )
+                    .col(ColumnDef::new(Invitation::CreatedBy).integer().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-invitation-created_by")
+                            .from(Invitation::Table, Invitation::CreatedBy)
+                            .to(User::Table, User::Id),
+                    )
+                    .col(ColumnDef::new(Invitation::UsedBy).integer().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-invitation-used_by")
+                            .from(Invitation::Table, Invitation::UsedBy)
+                            .to(User::Table, User::Id),
+                    )
+                    .col(ColumnDef::new(Invitation::UsedAt).timestamp().null())
+                    .col(ColumnDef::new(Invitation::ExpiresAt).timestamp().null())
+                    .col(
+                        ColumnDef::new(Invitation::CreatedAt)
+                            .timestamp()
+                            .extra("DEFAULT CURRENT_TIMESTAMP".to_owned()),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Invitation::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Invitation {
+    Table,
+    Id,
+    CodeHash,
+    Role,
+    CreatedBy,
+    UsedBy,
+    UsedAt,
+    ExpiresAt,
+    CreatedAt,
+}
@@ -0,0 +1,59 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Book::Table)
+                    .add_column(ColumnDef::new(Book::Isbn13).char_len(13).null())
+                    .add_column(ColumnDef::new(Book::Isbn10).char_len(10).null()) // 979开头的ISBN-13没有对应的ISBN-10
+                    .to_owned(),
+            )
+            .await?;
+
+        // ISBN-10都能转换为ISBN-13，只需要对ISBN-13建唯一索引
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-book-isbn13")
+                    .table(Book::Table)
+                    .col(Book::Isbn13)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-book-isbn13")
+                    .table(Book::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Book::Table)
+                    .drop_column(Book::Isbn13)
+                    .drop_column(Book::Isbn10)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Book {
+    Table,
+    Isbn13,
+    Isbn10,
+}
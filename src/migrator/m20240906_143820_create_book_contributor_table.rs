@@ -0,0 +1,105 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20240704_155437_create_author_table::Author;
+use super::m20240704_160757_create_book_table::Book;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(BookContributor::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(BookContributor::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(BookContributor::BookId).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-book_contributor-book_id")
+                            .from(BookContributor::Table, BookContributor::BookId)
+                            .to(Book::Table, Book::Id),
+                    )
+                    .col(
+                        ColumnDef::new(BookContributor::AuthorId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-book_contributor-author_id")
+                            .from(BookContributor::Table, BookContributor::AuthorId)
+                            .to(Author::Table, Author::Id),
+                    )
+                    .col(
+                        ColumnDef::new(BookContributor::Role)
+                            .string_len(16)
+                            .not_null()
+                            .default("author"), // author、editor、translator或illustrator
+                    )
+                    .col(
+                        ColumnDef::new(BookContributor::Position)
+                            .integer()
+                            .not_null()
+                            .default(0), // 署名顺序
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx-book_contributor-book_id-author_id-role")
+                            .col(BookContributor::BookId)
+                            .col(BookContributor::AuthorId)
+                            .col(BookContributor::Role)
+                            .unique(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // 已有书籍的作者作为第一位署名作者
+        let insert = Query::insert()
+            .into_table(BookContributor::Table)
+            .columns([
+                BookContributor::BookId,
+                BookContributor::AuthorId,
+                BookContributor::Role,
+                BookContributor::Position,
+            ])
+            .select_from(
+                Query::select()
+                    .column(Book::Id)
+                    .column(Book::AuthorId)
+                    .expr(Expr::val("author"))
+                    .expr(Expr::val(0))
+                    .from(Book::Table)
+                    .to_owned(),
+            )
+            .map_err(|e| DbErr::Migration(e.to_string()))?
+            .to_owned();
+
+        manager.exec_stmt(insert).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(BookContributor::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum BookContributor {
+    Table,
+    Id,
+    BookId,
+    AuthorId,
+    Role,
+    Position,
+}
@@ -0,0 +1,63 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20240704_160757_create_book_table::Book;
+use super::m20240910_093145_create_genre_table::Genre;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(BookGenre::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(BookGenre::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(BookGenre::BookId).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-book_genre-book_id")
+                            .from(BookGenre::Table, BookGenre::BookId)
+                            .to(Book::Table, Book::Id),
+                    )
+                    .col(ColumnDef::new(BookGenre::GenreId).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-book_genre-genre_id")
+                            .from(BookGenre::Table, BookGenre::GenreId)
+                            .to(Genre::Table, Genre::Id),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx-book_genre-book_id-genre_id")
+                            .col(BookGenre::BookId)
+                            .col(BookGenre::GenreId)
+                            .unique(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(BookGenre::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum BookGenre {
+    Table,
+    Id,
+    BookId,
+    GenreId,
+}
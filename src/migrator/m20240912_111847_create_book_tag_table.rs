@@ -0,0 +1,78 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20220101_000001_create_user_table::User;
+use super::m20240704_160757_create_book_table::Book;
+use super::m20240912_110530_create_tag_table::Tag;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(BookTag::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(BookTag::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(BookTag::BookId).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-book_tag-book_id")
+                            .from(BookTag::Table, BookTag::BookId)
+                            .to(Book::Table, Book::Id),
+                    )
+                    .col(ColumnDef::new(BookTag::TagId).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-book_tag-tag_id")
+                            .from(BookTag::Table, BookTag::TagId)
+                            .to(Tag::Table, Tag::Id),
+                    )
+                    .col(ColumnDef::new(BookTag::UserId).integer().null()) // 添加标签的用户
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-book_tag-user_id")
+                            .from(BookTag::Table, BookTag::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .col(
+                        ColumnDef::new(BookTag::CreatedAt)
+                            .timestamp()
+                            .extra("DEFAULT CURRENT_TIMESTAMP".to_owned()),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx-book_tag-book_id-tag_id")
+                            .col(BookTag::BookId)
+                            .col(BookTag::TagId)
+                            .unique(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(BookTag::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum BookTag {
+    Table,
+    Id,
+    BookId,
+    TagId,
+    UserId,
+    CreatedAt,
+}
@@ -3,6 +3,25 @@ pub use sea_orm_migration::prelude::*;
 mod m20220101_000001_create_user_table;
 mod m20240704_155437_create_author_table;
 mod m20240704_160757_create_book_table;
+mod m20240712_093015_add_role_to_user_table;
+mod m20240715_141208_create_refresh_token_table;
+mod m20240718_103442_create_revoked_token_table;
+mod m20240722_160315_create_password_reset_token_table;
+mod m20240726_111754_create_email_verification_table;
+mod m20240730_084521_add_login_lockout_to_user_table;
+mod m20240805_152037_add_two_factor_to_user_table;
+mod m20240812_101530_create_api_key_table;
+mod m20240816_173402_create_email_change_table;
+mod m20240820_094512_add_disabled_at_to_user_table;
+mod m20240823_140218_create_user_identity_table;
+mod m20240827_103655_create_auth_event_table;
+mod m20240830_161047_create_invitation_table;
+mod m20240903_102416_add_isbn_to_book_table;
+mod m20240906_143820_create_book_contributor_table;
+mod m20240910_093145_create_genre_table;
+mod m20240910_094212_create_book_genre_table;
+mod m20240912_110530_create_tag_table;
+mod m20240912_111847_create_book_tag_table;
 
 pub struct Migrator;
 
@@ -13,6 +32,25 @@ impl MigratorTrait for Migrator {
             Box::new(m20220101_000001_create_user_table::Migration),
             Box::new(m20240704_155437_create_author_table::Migration),
             Box::new(m20240704_160757_create_book_table::Migration),
+            Box::new(m20240712_093015_add_role_to_user_table::Migration),
+            Box::new(m20240715_141208_create_refresh_token_table::Migration),
+            Box::new(m20240718_103442_create_revoked_token_table::Migration),
+            Box::new(m20240722_160315_create_password_reset_token_table::Migration),
+            Box::new(m20240726_111754_create_email_verification_table::Migration),
+            Box::new(m20240730_084521_add_login_lockout_to_user_table::Migration),
+            Box::new(m20240805_152037_add_two_factor_to_user_table::Migration),
+            Box::new(m20240812_101530_create_api_key_table::Migration),
+            Box::new(m20240816_173402_create_email_change_table::Migration),
+            Box::new(m20240820_094512_add_disabled_at_to_user_table::Migration),
+            Box::new(m20240823_140218_create_user_identity_table::Migration),
+            Box::new(m20240827_103655_create_auth_event_table::Migration),
+            Box::new(m20240830_161047_create_invitation_table::Migration),
+            Box::new(m20240903_102416_add_isbn_to_book_table::Migration),
+            Box::new(m20240906_143820_create_book_contributor_table::Migration),
+            Box::new(m20240910_093145_create_genre_table::Migration),
+            Box::new(m20240910_094212_create_book_genre_table::Migration),
+            Box::new(m20240912_110530_create_tag_table::Migration),
+            Box::new(m20240912_111847_create_book_tag_table::Migration),
         ]
     }
 }
@@ -0,0 +1,420 @@
+use std::{
+    collections::HashMap,
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use rocket::tokio::task;
+use sea_orm::*;
+use tantivy::{
+    collector::{Count, TopDocs},
+    directory::MmapDirectory,
+    doc,
+    query::{BooleanQuery, BoostQuery, FuzzyTermQuery, Occur, Query, QueryParser},
+    schema::{Field, Schema, TantivyDocument, Value, INDEXED, STORED, STRING, TEXT},
+    snippet::SnippetGenerator,
+    Index, IndexReader, IndexWriter, ReloadPolicy, Term,
+};
+
+use crate::controllers::books::credited_to;
+use crate::entities::{author, book, book_contributor, prelude::*};
+use crate::AppConfig;
+
+const WRITER_HEAP: usize = 50_000_000;
+const SNIPPET_CHARS: usize = 150;
+
+// 全文索引：书名、作者姓名和简介。
+// 数据库是唯一的数据源，索引可以随时通过rebuild重建。
+#[derive(Clone)]
+pub struct SearchIndex {
+    index: Index,
+    reader: IndexReader,
+    writer: Arc<Mutex<IndexWriter>>,
+    fields: Fields,
+}
+
+#[derive(Clone, Copy)]
+struct Fields {
+    key: Field, // "book:12"或"author:3"，用于更新和删除
+    kind: Field,
+    id: Field,
+    title: Field,
+    name: Field,
+    bio: Field,
+}
+
+pub struct SearchHit {
+    pub kind: String,
+    pub id: i32,
+    pub score: f32,
+    pub title: Option<String>,
+    pub name: Option<String>,
+    pub snippet: String,
+}
+
+fn schema() -> (Schema, Fields) {
+    let mut builder = Schema::builder();
+    let fields = Fields {
+        key: builder.add_text_field("key", STRING | STORED),
+        kind: builder.add_text_field("kind", STRING | STORED),
+        id: builder.add_u64_field("id", INDEXED | STORED),
+        title: builder.add_text_field("title", TEXT | STORED),
+        name: builder.add_text_field("name", TEXT | STORED),
+        bio: builder.add_text_field("bio", TEXT | STORED),
+    };
+
+    (builder.build(), fields)
+}
+
+fn full_name(a: &author::Model) -> String {
+    format!("{} {}", a.firstname, a.lastname)
+}
+
+// 书籍所有署名者的姓名，按署名顺序排列
+async fn contributor_names(
+    db: &DatabaseConnection,
+    book_ids: Option<Vec<i32>>,
+) -> Result<HashMap<i32, Vec<String>>, DbErr> {
+    let mut select = BookContributor::find();
+    if let Some(ids) = book_ids {
+        select = select.filter(book_contributor::Column::BookId.is_in(ids));
+    }
+
+    let mut names: HashMap<i32, Vec<String>> = HashMap::new();
+    for (c, a) in select
+        .order_by_asc(book_contributor::Column::Position)
+        .order_by_asc(book_contributor::Column::Id)
+        .find_also_related(Author)
+        .all(db)
+        .await?
+    {
+        if let Some(a) = a {
+            names.entry(c.book_id).or_default().push(full_name(&a));
+        }
+    }
+
+    Ok(names)
+}
+
+impl SearchIndex {
+    pub fn open(config: &AppConfig) -> Self {
+        fs::create_dir_all(&config.search_index_dir).expect("[-] 无法创建搜索索引目录");
+
+        let (schema, fields) = schema();
+        let dir = MmapDirectory::open(&config.search_index_dir).expect("[-] 无法打开搜索索引目录");
+        let index = Index::open_or_create(dir, schema).expect("[-] 无法打开搜索索引");
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .expect("[-] 无法读取搜索索引");
+        let writer = index
+            .writer(WRITER_HEAP)
+            .expect("[-] 无法写入搜索索引，是否有其他进程正在使用？");
+
+        Self {
+            index,
+            reader,
+            writer: Arc::new(Mutex::new(writer)),
+            fields,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reader.searcher().num_docs() == 0
+    }
+
+    fn book_doc(&self, b: &book::Model, names: Option<&Vec<String>>) -> TantivyDocument {
+        let f = self.fields;
+        doc!(
+            f.key => format!("book:{}", b.id),
+            f.kind => "book",
+            f.id => b.id as u64,
+            f.title => b.title.to_owned(),
+            f.name => names.map(|n| n.join(", ")).unwrap_or_default(),
+        )
+    }
+
+    fn author_doc(&self, a: &author::Model) -> TantivyDocument {
+        let f = self.fields;
+        doc!(
+            f.key => format!("author:{}", a.id),
+            f.kind => "author",
+            f.id => a.id as u64,
+            f.name => full_name(a),
+            f.bio => a.bio.to_owned(),
+        )
+    }
+
+    // 修改索引并提交，提交后立即对搜索可见。
+    // 提交需要写盘，放到阻塞线程中执行，避免占用异步运行时的工作线程
+    async fn commit<F>(&self, f: F) -> Result<(), String>
+    where
+        F: FnOnce(&mut IndexWriter) -> tantivy::Result<()> + Send + 'static,
+    {
+        let writer = Arc::clone(&self.writer);
+        let reader = self.reader.clone();
+
+        task::spawn_blocking(move || {
+            let mut writer = writer.lock().unwrap();
+            f(&mut writer)?;
+            writer.commit()?;
+            reader.reload()
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+    }
+
+    // 先删除旧文档再写入新文档
+    async fn write(&self, keys: Vec<String>, docs: Vec<TantivyDocument>) -> Result<(), String> {
+        let field = self.fields.key;
+
+        self.commit(move |writer| {
+            for key in keys {
+                writer.delete_term(Term::from_field_text(field, &key));
+            }
+            for doc in docs {
+                writer.add_document(doc)?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    // 索引失败不影响请求，索引可以稍后重建
+    fn log(result: Result<(), String>) {
+        if let Err(err) = result {
+            error!("更新搜索索引失败：{}", err);
+        }
+    }
+
+    async fn try_sync_book(&self, db: &DatabaseConnection, id: i32) -> Result<(), String> {
+        let key = format!("book:{}", id);
+
+        let b = match Book::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(|e| e.to_string())?
+        {
+            Some(b) => b,
+            None => return self.write(vec![key], vec![]).await,
+        };
+        let names = contributor_names(db, Some(vec![id]))
+            .await
+            .map_err(|e| e.to_string())?;
+
+        self.write(vec![key], vec![self.book_doc(&b, names.get(&id))])
+            .await
+    }
+
+    // 作者的姓名也写在其署名书籍的文档中，需要一起更新
+    async fn try_sync_author(&self, db: &DatabaseConnection, id: i32) -> Result<(), String> {
+        let mut keys = vec![format!("author:{}", id)];
+        let mut docs = vec![];
+
+        if let Some(a) = Author::find_by_id(id)
+            .one(db)
+            .await
+            .map_err(|e| e.to_string())?
+        {
+            docs.push(self.author_doc(&a));
+
+            let books = Book::find()
+                .filter(credited_to(id))
+                .all(db)
+                .await
+                .map_err(|e| e.to_string())?;
+            let names = contributor_names(db, Some(books.iter().map(|b| b.id).collect()))
+                .await
+                .map_err(|e| e.to_string())?;
+            for b in books {
+                keys.push(format!("book:{}", b.id));
+                docs.push(self.book_doc(&b, names.get(&b.id)));
+            }
+        }
+
+        self.write(keys, docs).await
+    }
+
+    pub async fn sync_book(&self, db: &DatabaseConnection, id: i32) {
+        Self::log(self.try_sync_book(db, id).await);
+    }
+
+    pub async fn sync_author(&self, db: &DatabaseConnection, id: i32) {
+        Self::log(self.try_sync_author(db, id).await);
+    }
+
+    pub async fn remove(&self, book_ids: &[i32], author_ids: &[i32]) {
+        let keys = book_ids
+            .iter()
+            .map(|id| format!("book:{}", id))
+            .chain(author_ids.iter().map(|id| format!("author:{}", id)))
+            .collect::<Vec<_>>();
+
+        Self::log(self.write(keys, vec![]).await);
+    }
+
+    // 清空索引并从数据库重新写入全部书籍和作者
+    pub async fn rebuild(&self, db: &DatabaseConnection) -> Result<usize, String> {
+        let authors = Author::find().all(db).await.map_err(|e| e.to_string())?;
+        let books = Book::find().all(db).await.map_err(|e| e.to_string())?;
+        let names = contributor_names(db, None)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let docs = authors
+            .iter()
+            .map(|a| self.author_doc(a))
+            .chain(books.iter().map(|b| self.book_doc(b, names.get(&b.id))))
+            .collect::<Vec<_>>();
+        let count = docs.len();
+
+        self.commit(move |writer| {
+            writer.delete_all_documents()?;
+            for doc in docs {
+                writer.add_document(doc)?;
+            }
+            Ok(())
+        })
+        .await?;
+
+        Ok(count)
+    }
+
+    // 查询词的拼写容错：编辑距离不超过1（长词为2），由tantivy用自动机在词典中匹配
+    fn fuzzy_queries(&self, text: &str) -> Result<Vec<Box<dyn Query>>, String> {
+        let mut words = vec![];
+        let mut tokenizer = self
+            .index
+            .tokenizer_for_field(self.fields.title)
+            .map_err(|e| e.to_string())?;
+        let mut stream = tokenizer.token_stream(text);
+        while stream.advance() {
+            words.push(stream.token().text.to_owned());
+        }
+        words.retain(|w| w.chars().count() >= 3);
+
+        let mut queries: Vec<Box<dyn Query>> = vec![];
+        for field in [self.fields.title, self.fields.name, self.fields.bio] {
+            for w in &words {
+                let distance = if w.chars().count() >= 8 { 2 } else { 1 };
+                queries.push(Box::new(FuzzyTermQuery::new(
+                    Term::from_field_text(field, w),
+                    distance,
+                    true,
+                )));
+            }
+        }
+
+        Ok(queries)
+    }
+
+    // 查询和生成片段都是CPU密集的同步操作，放到阻塞线程中执行。
+    // 克隆只复制内部的Arc，代价很小
+    pub async fn search(
+        &self,
+        text: String,
+        limit: usize,
+    ) -> Result<(usize, Vec<SearchHit>), String> {
+        let index = self.clone();
+
+        task::spawn_blocking(move || index.search_blocking(&text, limit))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
+    fn search_blocking(&self, text: &str, limit: usize) -> Result<(usize, Vec<SearchHit>), String> {
+        let f = self.fields;
+        let searcher = self.reader.searcher();
+
+        // 书名和姓名的权重高于简介
+        let mut parser = QueryParser::for_index(&self.index, vec![f.title, f.name, f.bio]);
+        parser.set_field_boost(f.title, 3.0);
+        parser.set_field_boost(f.name, 2.0);
+        let (exact, _) = parser.parse_query_lenient(text);
+
+        // 拼写相近的词得分低于原词
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> =
+            vec![(Occur::Should, Box::new(BoostQuery::new(exact, 2.0)))];
+        for query in self.fuzzy_queries(text)? {
+            clauses.push((Occur::Should, query));
+        }
+        let query = BooleanQuery::new(clauses);
+
+        let (top, total) = searcher
+            .search(&query, &(TopDocs::with_limit(limit), Count))
+            .map_err(|e| e.to_string())?;
+
+        let snippets = [f.title, f.name, f.bio]
+            .into_iter()
+            .map(|field| {
+                SnippetGenerator::create(&searcher, &query, field).map(|mut g| {
+                    g.set_max_num_chars(SNIPPET_CHARS);
+                    (field, g)
+                })
+            })
+            .collect::<tantivy::Result<HashMap<_, _>>>()
+            .map_err(|e| e.to_string())?;
+
+        let mut hits = vec![];
+        for (score, address) in top {
+            let doc = searcher
+                .doc::<TantivyDocument>(address)
+                .map_err(|e| e.to_string())?;
+            let text = |field: Field| {
+                doc.get_first(field)
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string())
+            };
+            let kind = text(f.kind).unwrap_or_default();
+
+            // 书籍优先展示书名的片段，作者优先展示简介的片段
+            let order = if kind == "book" {
+                [f.title, f.name]
+            } else {
+                [f.bio, f.name]
+            };
+            let snippet = order
+                .iter()
+                .map(|field| snippets[field].snippet_from_doc(&doc))
+                .find(|s| !s.is_empty())
+                .map(|s| s.to_html())
+                .unwrap_or_else(|| {
+                    let mut s = text(order[0]).unwrap_or_default();
+                    if let Some((i, _)) = s.char_indices().nth(SNIPPET_CHARS) {
+                        s.truncate(i);
+                    }
+                    escape_html(&s)
+                });
+
+            hits.push(SearchHit {
+                id: doc.get_first(f.id).and_then(|v| v.as_u64()).unwrap_or(0) as i32,
+                score,
+                title: text(f.title),
+                name: text(f.name),
+                snippet,
+                kind,
+            });
+        }
+
+        Ok((total, hits))
+    }
+}
+
+// 片段作为HTML返回，没有匹配时的原文也要转义
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}